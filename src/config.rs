@@ -1,16 +1,50 @@
 use meilisearch_sdk::Client;
 use serde::Deserialize;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::middlewares::auth::Role;
 
 pub const CONFIG_FILE: &str = "config.toml";
 pub const DEFAULT_PORT: u16 = 80;
+pub const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 30;
 pub const DEFAULT_MONGO_PORT: u16 = 27017;
 pub const DEFAULT_REDIS_PORT: u16 = 6379;
+pub const DEFAULT_TOKEN_PREFIX: &str = "tk";
+pub const DEFAULT_TOKEN_LENGTH: u8 = 42;
+pub const MIN_TOKEN_LENGTH: u8 = 16;
+pub const DEFAULT_SESSION_TTL_SECS: u64 = 7 * 24 * 60 * 60; // 7 days
+pub const DEFAULT_SESSION_CACHE_TTL_SECS: u64 = 5;
+pub const DEFAULT_SESSION_CACHE_SIZE: usize = 1024;
+pub const DEFAULT_MAX_POSTER_BYTES: usize = 10 * 1024 * 1024; // 10 MiB
+pub const DEFAULT_PLACEHOLDER_COMPONENTS_X: usize = 4;
+pub const DEFAULT_PLACEHOLDER_COMPONENTS_Y: usize = 7;
+pub const DEFAULT_MAX_CONCURRENT_IMAGE_JOBS: usize = 2; // when the core count is unknown
+pub const MAX_PLACEHOLDER_COMPONENTS: usize = 9; // blurhash limit per axis
+pub const DEFAULT_IMAGES_MAX_AGE_SECS: u32 = 365 * 24 * 60 * 60; // 1 year
+pub const DEFAULT_DETAILS_MAX_AGE_SECS: u32 = 5 * 60; // 5 minutes
+pub const DEFAULT_SEARCH_MAX_AGE_SECS: u32 = 60; // 1 minute
+pub const DEFAULT_ORPHAN_GRACE_SECS: u64 = 60 * 60; // 1 hour
+pub const DEFAULT_MEILISEARCH_TIMEOUT_MS: u64 = 2000;
+pub const DEFAULT_MEILISEARCH_HEALTH_CHECK_INTERVAL_SECS: u64 = 30;
+pub const DEFAULT_SEARCH_DEFAULT_LIMIT: u32 = 10;
+pub const DEFAULT_SEARCH_SOFT_LIMIT: u32 = 100;
+pub const DEFAULT_SEARCH_PRIVILEGED_SOFT_LIMIT: u32 = 1000;
+pub const DEFAULT_MAX_TITLES: usize = 32;
+pub const DEFAULT_MAX_TITLE_LENGTH: usize = 256;
+pub const MAX_BROTLI_QUALITY: u32 = 11;
+pub const MAX_GZIP_LEVEL: u32 = 9;
+pub const DEFAULT_SCHEME: &str = "https";
+pub const DEFAULT_LIST_SORT: &str = "updatedOn";
 
 #[derive(Deserialize)]
 pub struct Config<'ha, 'moa, 'mob, 'moc, 'msa, 'msb, 'cf, 'd> {
     pub debug: Option<bool>,
     #[serde(borrow)]
     pub domain: &'d str,
+    /// Scheme of the public URLs built from the domain, `http` or `https`. When unset, URLs built
+    /// while answering a request use the scheme the client used and the others use `https`.
+    pub scheme: Option<String>,
     #[serde(borrow)]
     pub cache_folder: &'cf str,
 
@@ -21,12 +55,64 @@ pub struct Config<'ha, 'moa, 'mob, 'moc, 'msa, 'msb, 'cf, 'd> {
     pub redis: RedisConfig,
     #[serde(borrow)]
     pub meilisearch: MeilisearchConfig<'msa, 'msb>,
+    #[serde(default)]
+    pub images: ImagesConfig,
+    #[serde(default)]
+    pub auth: AuthConfig,
+    #[serde(default)]
+    pub cache: CacheConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub search: SearchConfig,
+    #[serde(default)]
+    pub compression: CompressionConfig,
+    #[serde(default)]
+    pub catalog: CatalogConfig,
+    #[serde(default)]
+    pub list: ListConfig,
+}
+
+impl Config<'_, '_, '_, '_, '_, '_, '_, '_> {
+    pub fn scheme(&self) -> &str {
+        self.scheme.as_deref().unwrap_or(DEFAULT_SCHEME)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !matches!(self.scheme(), "http" | "https") {
+            return Err(format!("scheme must be `http` or `https`, found `{}`", self.scheme()));
+        }
+        self.images.validate()?;
+        self.auth.validate()?;
+        self.search.validate()?;
+        self.compression.validate()?;
+        self.catalog.validate()?;
+        self.list.validate()
+    }
 }
 
 #[derive(Deserialize, Clone)]
 pub struct HttpConfig<'a> {
     pub host: &'a str,
     pub port: Option<u16>,
+    pub workers: Option<usize>,
+    pub keep_alive_secs: Option<u64>,
+    /// Bounds the time spent producing a response, streamed bodies can take longer to send
+    pub request_timeout_secs: Option<u64>,
+    #[serde(borrow)]
+    pub tls: Option<TlsConfig<'a>>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct TlsConfig<'a> {
+    pub cert: &'a str,
+    pub key: &'a str,
+}
+
+impl HttpConfig<'_> {
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request_timeout_secs.unwrap_or(DEFAULT_REQUEST_TIMEOUT_SECS))
+    }
 }
 
 impl From<HttpConfig<'_>> for (String, u16) {
@@ -41,6 +127,9 @@ pub struct MongoDBConfig<'a, 'b, 'c> {
     pub port: Option<u16>,
     pub username: &'b str,
     pub password: &'c str,
+    pub max_pool_size: Option<u32>,
+    pub min_pool_size: Option<u32>,
+    pub connect_timeout_ms: Option<u64>,
 }
 
 impl MongoDBConfig<'_, '_, '_> {
@@ -50,12 +139,25 @@ impl MongoDBConfig<'_, '_, '_> {
         uri.push_str(&url_escape::encode_fragment(app_name));
         uri
     }
+
+    /// Overrides the driver defaults with the configured pool settings, if any
+    pub fn apply_pool_options(&self, options: &mut mongodb::options::ClientOptions) {
+        if let Some(max) = self.max_pool_size {
+            options.max_pool_size = Some(max);
+        }
+        if let Some(min) = self.min_pool_size {
+            options.min_pool_size = Some(min);
+        }
+        if let Some(ms) = self.connect_timeout_ms {
+            options.connect_timeout = Some(std::time::Duration::from_millis(ms));
+        }
+    }
 }
 
-impl ToString for MongoDBConfig<'_, '_, '_> {
-    fn to_string(&self) -> String {
+impl std::fmt::Display for MongoDBConfig<'_, '_, '_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         use url_escape::{encode_fragment, encode_path};
-        format!("mongodb://{}:{}@{}:{}/",
+        write!(f, "mongodb://{}:{}@{}:{}/",
                 encode_fragment(self.username),
                 encode_fragment(self.password),
                 encode_path(self.host),
@@ -97,11 +199,371 @@ impl redis::IntoConnectionInfo for RedisConfig {
 pub struct MeilisearchConfig<'a, 'b> {
     pub host: &'a str,
     pub master_key: &'b str,
-    pub auto_sync: Option<bool>
+    pub auto_sync: Option<bool>,
+    /// Applies to each search request sent on behalf of a client
+    pub timeout_ms: Option<u64>,
+    /// How often Meilisearch is checked after startup, `0` disables the checks. The index is
+    /// synced when it comes back up, if `auto_sync` is enabled.
+    pub health_check_interval_secs: Option<u64>,
 }
 
 impl MeilisearchConfig<'_, '_> {
     pub fn as_client(&self) -> Client {
         Client::new(self.host, self.master_key)
     }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms.unwrap_or(DEFAULT_MEILISEARCH_TIMEOUT_MS))
+    }
+
+    pub fn auto_sync(&self) -> bool {
+        self.auto_sync.unwrap_or(true)
+    }
+
+    pub fn health_check_interval(&self) -> Option<Duration> {
+        match self.health_check_interval_secs.unwrap_or(DEFAULT_MEILISEARCH_HEALTH_CHECK_INTERVAL_SECS) {
+            0 => None,
+            secs => Some(Duration::from_secs(secs))
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub struct ImagesConfig {
+    pub max_poster_bytes: Option<usize>,
+    #[serde(default)]
+    pub resize_algorithm: ResizeFilter,
+    pub placeholder_components_x: Option<usize>,
+    pub placeholder_components_y: Option<usize>,
+    /// Posters and presenters generated at once by the whole process, defaults to the number of
+    /// available cores
+    pub max_concurrent_image_jobs: Option<usize>,
+    /// Whether presenters are rendered, their template and fonts are not needed when disabled
+    pub enable_presenter: Option<bool>,
+    /// Image served in place of a missing poster or presenter, and pasted on the presenters of
+    /// animes without a poster
+    pub fallback_poster: Option<PathBuf>,
+}
+
+impl ImagesConfig {
+    pub fn max_poster_bytes(&self) -> usize {
+        self.max_poster_bytes.unwrap_or(DEFAULT_MAX_POSTER_BYTES)
+    }
+
+    pub fn max_concurrent_image_jobs(&self) -> usize {
+        self.max_concurrent_image_jobs.unwrap_or_else(|| std::thread::available_parallelism()
+            .map_or(DEFAULT_MAX_CONCURRENT_IMAGE_JOBS, usize::from))
+    }
+
+    pub fn presenter_enabled(&self) -> bool {
+        self.enable_presenter.unwrap_or(true)
+    }
+
+    /// Blurhash components of the poster placeholders, horizontally then vertically
+    pub fn placeholder_components(&self) -> (usize, usize) {
+        (self.placeholder_components_x.unwrap_or(DEFAULT_PLACEHOLDER_COMPONENTS_X),
+            self.placeholder_components_y.unwrap_or(DEFAULT_PLACEHOLDER_COMPONENTS_Y))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let (x, y) = self.placeholder_components();
+        let range = 1..=MAX_PLACEHOLDER_COMPONENTS;
+        if !range.contains(&x) || !range.contains(&y) {
+            return Err(format!("images.placeholder_components_x and images.placeholder_components_y \
+                must be between 1 and {MAX_PLACEHOLDER_COMPONENTS}"));
+        }
+        if self.max_concurrent_image_jobs == Some(0) {
+            return Err("images.max_concurrent_image_jobs must be at least 1".to_string());
+        }
+        if matches!(&self.fallback_poster, Some(path) if !path.is_file()) {
+            return Err("images.fallback_poster must be an existing image file".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Mirrors [`ril::ResizeAlgorithm`], from the fastest to the best looking one
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ResizeFilter {
+    Nearest,
+    Box,
+    Bilinear,
+    Hamming,
+    Bicubic,
+    Mitchell,
+    #[default]
+    Lanczos3,
+}
+
+impl From<ResizeFilter> for ril::ResizeAlgorithm {
+    fn from(value: ResizeFilter) -> Self {
+        match value {
+            ResizeFilter::Nearest => Self::Nearest,
+            ResizeFilter::Box => Self::Box,
+            ResizeFilter::Bilinear => Self::Bilinear,
+            ResizeFilter::Hamming => Self::Hamming,
+            ResizeFilter::Bicubic => Self::Bicubic,
+            ResizeFilter::Mitchell => Self::Mitchell,
+            ResizeFilter::Lanczos3 => Self::Lanczos3,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub struct AuthConfig {
+    pub token_prefix: Option<String>,
+    pub token_length: Option<u8>,
+    /// Allows issuing sessions through `POST /s/session` outside of debug mode
+    pub bootstrap_key: Option<String>,
+    pub session_ttl_secs: Option<u64>,
+    /// Rejects anonymous requests outside of `/s/` (already guarded by role), for private betas
+    #[serde(default)]
+    pub require_auth_for_reads: bool,
+    /// Answers `404` instead of `401`/`403` when a role guard rejects a request, so that anonymous
+    /// clients cannot probe which `/s/` routes exist
+    #[serde(default)]
+    pub hide_guarded_routes: bool,
+    /// How long a session read from Redis is reused by this process, `0` disables the cache. A
+    /// session revoked through another instance stays usable here for that long.
+    pub session_cache_ttl_secs: Option<u64>,
+    /// Sessions kept in memory at most, the least recently used ones are evicted first
+    pub session_cache_size: Option<usize>,
+    /// Least role allowed to sync the search index, defaults to `admin`. Reindexing and every
+    /// destructive operation stay admin-only.
+    pub sync_min_role: Option<Role>,
+    /// Least role allowed to rebuild the sitemap, defaults to `admin`
+    pub seo_min_role: Option<Role>,
+}
+
+impl AuthConfig {
+    pub fn token_prefix(&self) -> &str {
+        self.token_prefix.as_deref().unwrap_or(DEFAULT_TOKEN_PREFIX)
+    }
+
+    pub fn token_length(&self) -> u8 {
+        self.token_length.unwrap_or(DEFAULT_TOKEN_LENGTH)
+    }
+
+    pub fn session_ttl_secs(&self) -> u64 {
+        self.session_ttl_secs.unwrap_or(DEFAULT_SESSION_TTL_SECS)
+    }
+
+    pub fn session_cache_ttl(&self) -> Duration {
+        Duration::from_secs(self.session_cache_ttl_secs.unwrap_or(DEFAULT_SESSION_CACHE_TTL_SECS))
+    }
+
+    pub fn session_cache_size(&self) -> usize {
+        self.session_cache_size.unwrap_or(DEFAULT_SESSION_CACHE_SIZE)
+    }
+
+    pub fn sync_min_role(&self) -> Role {
+        self.sync_min_role.unwrap_or(Role::Admin)
+    }
+
+    pub fn seo_min_role(&self) -> Role {
+        self.seo_min_role.unwrap_or(Role::Admin)
+    }
+
+    /// Whoever may start a job can follow it by id, listing every job stays admin-only
+    pub fn job_viewer_min_role(&self) -> Role {
+        self.sync_min_role().min(self.seo_min_role())
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.token_length() < MIN_TOKEN_LENGTH {
+            return Err(format!("auth.token_length must be at least {MIN_TOKEN_LENGTH}"));
+        }
+        if self.token_prefix().is_empty() {
+            return Err("auth.token_prefix must not be empty".to_string());
+        }
+        if matches!(&self.bootstrap_key, Some(key) if key.len() < MIN_TOKEN_LENGTH as usize) {
+            return Err(format!("auth.bootstrap_key must be at least {MIN_TOKEN_LENGTH} characters"));
+        }
+        if self.sync_min_role() < Role::Mod || self.seo_min_role() < Role::Mod {
+            return Err("auth.sync_min_role and auth.seo_min_role must be mod or admin".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub struct CacheConfig {
+    pub images_max_age_secs: Option<u32>,
+    pub details_max_age_secs: Option<u32>,
+    pub search_max_age_secs: Option<u32>,
+    /// Cached images younger than this are never collected, they may belong to an anime being
+    /// pushed
+    pub orphan_grace_secs: Option<u64>,
+}
+
+impl CacheConfig {
+    pub fn images_max_age(&self) -> u32 {
+        self.images_max_age_secs.unwrap_or(DEFAULT_IMAGES_MAX_AGE_SECS)
+    }
+
+    pub fn details_max_age(&self) -> u32 {
+        self.details_max_age_secs.unwrap_or(DEFAULT_DETAILS_MAX_AGE_SECS)
+    }
+
+    pub fn search_max_age(&self) -> u32 {
+        self.search_max_age_secs.unwrap_or(DEFAULT_SEARCH_MAX_AGE_SECS)
+    }
+
+    pub fn orphan_grace(&self) -> Duration {
+        Duration::from_secs(self.orphan_grace_secs.unwrap_or(DEFAULT_ORPHAN_GRACE_SECS))
+    }
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub struct SearchConfig {
+    /// Page size used when the query does not specify a limit
+    pub default_limit: Option<u32>,
+    /// Highest page size for anonymous users and the `user` role
+    pub soft_limit: Option<u32>,
+    /// Highest page size for the `mod` and `admin` roles
+    pub privileged_soft_limit: Option<u32>,
+    /// Attributes searched by the index, by decreasing importance. When set, the index is made to
+    /// match on each sync (changes done through the admin endpoint are reverted), and the
+    /// attributes the API relies on are appended if missing.
+    pub searchable_attributes: Option<Vec<String>>,
+    /// Same as `searchable_attributes`, for the attributes results can be sorted on
+    pub sortable_attributes: Option<Vec<String>>,
+    /// Same as `searchable_attributes`, for the attributes results can be filtered on
+    pub filterable_attributes: Option<Vec<String>>,
+}
+
+impl SearchConfig {
+    pub fn default_limit(&self) -> u32 {
+        self.default_limit.unwrap_or(DEFAULT_SEARCH_DEFAULT_LIMIT)
+    }
+
+    pub fn soft_limit(&self) -> u32 {
+        self.soft_limit.unwrap_or(DEFAULT_SEARCH_SOFT_LIMIT)
+    }
+
+    pub fn privileged_soft_limit(&self) -> u32 {
+        self.privileged_soft_limit.unwrap_or(DEFAULT_SEARCH_PRIVILEGED_SOFT_LIMIT)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.default_limit() == 0 {
+            return Err("search.default_limit must be at least 1".to_string());
+        }
+        if self.default_limit() > self.soft_limit() {
+            return Err("search.default_limit must not exceed search.soft_limit".to_string());
+        }
+        if self.soft_limit() > self.privileged_soft_limit() {
+            return Err("search.soft_limit must not exceed search.privileged_soft_limit".to_string());
+        }
+        let lists = [
+            ("searchable_attributes", &self.searchable_attributes),
+            ("sortable_attributes", &self.sortable_attributes),
+            ("filterable_attributes", &self.filterable_attributes),
+        ];
+        for (name, attributes) in lists {
+            let Some(attributes) = attributes else { continue };
+            if attributes.iter().any(|a| a.trim().is_empty() || a == "*") {
+                return Err(format!("search.{name} must only list attribute names"));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub struct LoggingConfig {
+    #[serde(default)]
+    pub format: LogFormat,
+}
+
+/// Compression settings for buffered text responses, unset algorithms keep the default encoder
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+pub struct CompressionConfig {
+    pub brotli_quality: Option<u32>,
+    pub gzip_level: Option<u32>,
+}
+
+impl CompressionConfig {
+    pub fn is_tuned(&self) -> bool {
+        self.brotli_quality.is_some() || self.gzip_level.is_some()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if matches!(self.brotli_quality, Some(q) if q > MAX_BROTLI_QUALITY) {
+            return Err(format!("compression.brotli_quality must be between 0 and {MAX_BROTLI_QUALITY}"));
+        }
+        if matches!(self.gzip_level, Some(l) if l > MAX_GZIP_LEVEL) {
+            return Err(format!("compression.gzip_level must be between 0 and {MAX_GZIP_LEVEL}"));
+        }
+        Ok(())
+    }
+}
+
+/// Bounds on what can be written to the catalog, they apply to titles and synonyms alike
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct CatalogConfig {
+    pub max_titles: Option<usize>,
+    pub max_title_length: Option<usize>,
+    /// Backs the duplicate detection with a unique index on the normalized titles, forced pushes
+    /// are then rejected as well. Turning it off again does not drop the index.
+    #[serde(default)]
+    pub unique_titles: bool,
+    /// Rejects candidates with fields the API does not know of, to catch typos in importers.
+    /// Defaults to strict in debug mode and lenient otherwise.
+    pub strict_candidates: Option<bool>,
+}
+
+impl CatalogConfig {
+    pub fn max_titles(&self) -> usize {
+        self.max_titles.unwrap_or(DEFAULT_MAX_TITLES)
+    }
+
+    /// In characters
+    pub fn max_title_length(&self) -> usize {
+        self.max_title_length.unwrap_or(DEFAULT_MAX_TITLE_LENGTH)
+    }
+
+    pub fn strict_candidates(&self, debug: bool) -> bool {
+        self.strict_candidates.unwrap_or(debug)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_titles() == 0 {
+            return Err("catalog.max_titles must be at least 1".to_string());
+        }
+        if self.max_title_length() == 0 {
+            return Err("catalog.max_title_length must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct ListConfig {
+    /// Sort of `GET /anime` when the query does not specify one, e.g. `-createdOn`
+    pub default_sort: Option<String>,
+}
+
+impl ListConfig {
+    pub fn default_sort(&self) -> &str {
+        self.default_sort.as_deref().unwrap_or(DEFAULT_LIST_SORT)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if crate::types::parse_list_sort(self.default_sort()).is_none() {
+            let names: Vec<&str> = crate::types::ANIME_LIST_SORTS.iter().map(|sort| sort.name).collect();
+            return Err(format!("list.default_sort must be one of {}, optionally prefixed with `-`",
+                names.join(", ")));
+        }
+        Ok(())
+    }
 }
@@ -1,10 +1,13 @@
 use anyhow::{Result, anyhow};
 use std::{fs::File, path::{Path, PathBuf}, io::{BufReader, BufWriter}};
-use std::time::Instant;
-use log::info;
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+use log::{info, warn};
 use ril::prelude::*;
-use ril::{Encoder, encodings::webp::WebPEncoder};
+use ril::encodings::webp::{WebPEncoderOptions, WebPStaticEncoder};
+use crate::config::ImagesConfig;
 use crate::types::{AnimeSeries, CachedImage};
+use super::layout::{PresenterLayout, ANIME_PRESENTER_LAYOUT};
 use fast_blurhash::{compute_dct_iter, base83};
 
 const ACCENT_COLOR: Rgb = Rgb::new(241, 143, 243);
@@ -18,64 +21,384 @@ const ANIME_POSTER_MEDIUM_HEIGHT: u32 = 468;
 const ANIME_POSTER_MEDIUM_QUALITY: f32 = 80.;
 
 const ANIME_PRESENTER_TEMPLATE: &str = "assets/templates/AnimePresenter.png";
+const ANIME_PRESENTER_FONT: &str = "assets/fonts/Poppins-ExtraBold.ttf";
 const ANIME_PRESENTER_TEMPLATE_FORMAT: ImageFormat = ImageFormat::Png;
 const ANIME_PRESENTER_FOLDER: &str = "pre";
-
-const ANIME_PLACEHOLDER_COMPONENTS_X: usize = 4;
-const ANIME_PLACEHOLDER_COMPONENTS_Y: usize = 7;
+const ANIME_PRESENTER_UNTITLED: &str = "Untitled";
+
+/// Every folder images are generated into, relative to the cache folder
+pub const ANIME_IMAGE_FOLDERS: [&str; 3] = [ANIME_POSTER_FULLRES_FOLDER, ANIME_POSTER_MEDIUM_FOLDER, ANIME_PRESENTER_FOLDER];
+/// Poster sizes that can be served, named after their folder
+pub const ANIME_POSTER_SIZES: [&str; 2] = [ANIME_POSTER_FULLRES_FOLDER, ANIME_POSTER_MEDIUM_FOLDER];
+const ANIME_TRANSCODED_FOLDER: &str = "transcoded";
+/// Content types accepted for uploaded posters with the format they are decoded with
+pub const SUPPORTED_POSTER_TYPES: [(&str, ImageFormat); 2] = [
+    ("image/webp", ImageFormat::WebP),
+    ("image/png", ImageFormat::Png),
+];
+
+/// Format to decode an uploaded poster with, `None` if its content type is not supported
+pub fn poster_format(content_type: &str) -> Option<ImageFormat> {
+    SUPPORTED_POSTER_TYPES.iter()
+        .find(|(supported, _)| *supported == content_type)
+        .map(|(_, format)| *format)
+}
 
 #[allow(dead_code)]
 pub fn get_fullres_path(key: &str, cache_folder: &Path) -> PathBuf {
     cache_folder.join(ANIME_POSTER_FULLRES_FOLDER).join(format!("{key}.webp"))
 }
 
-pub fn export_poster(cache_key: String, from: &Path, cache_folder: &Path) -> Result<CachedImage> {
+/// Assets the presenters are rendered from that cannot be found, the layout has a built-in default
+pub fn missing_presenter_assets() -> Vec<&'static str> {
+    [ANIME_PRESENTER_TEMPLATE, ANIME_PRESENTER_FONT].into_iter()
+        .filter(|asset| !Path::new(asset).is_file())
+        .collect()
+}
+
+pub fn get_presenter_path(key: &str, cache_folder: &Path) -> PathBuf {
+    cache_folder.join(ANIME_PRESENTER_FOLDER).join(format!("{key}.webp"))
+}
+
+/// Formats posters can be served in, only WebP is stored and the others are transcoded from it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PosterFormat {
+    WebP,
+    Png,
+    Jpeg,
+}
+
+impl PosterFormat {
+    pub const TRANSCODED: [PosterFormat; 2] = [PosterFormat::Png, PosterFormat::Jpeg];
+
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "webp" => Some(Self::WebP),
+            "png" => Some(Self::Png),
+            "jpg" => Some(Self::Jpeg),
+            _ => None
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::WebP => "webp",
+            Self::Png => "png",
+            Self::Jpeg => "jpg",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::WebP => "image/webp",
+            Self::Png => "image/png",
+            Self::Jpeg => "image/jpeg",
+        }
+    }
+}
+
+fn get_transcoded_path(key: &str, size: &str, format: PosterFormat, cache_folder: &Path) -> PathBuf {
+    cache_folder.join(ANIME_TRANSCODED_FOLDER).join(size).join(format!("{key}.{}", format.extension()))
+}
+
+fn remove_cached_file(path: &Path) {
+    match std::fs::remove_file(path) {
+        Ok(()) => {},
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {},
+        Err(e) => warn!("Could not delete cached image `{}`: {e:?}", path.display())
+    }
+}
+
+/// Removes the transcoded copies of a poster, they must not outlive the WebP they come from
+fn delete_transcoded_images(key: &str, cache_folder: &Path) {
+    for size in ANIME_POSTER_SIZES {
+        for format in PosterFormat::TRANSCODED {
+            remove_cached_file(&get_transcoded_path(key, size, format, cache_folder));
+        }
+    }
+}
+
+/// Removes every generated image variant for the given poster key. Missing files are ignored
+/// and other failures are only logged.
+pub fn delete_cached_images(key: &str, cache_folder: &Path) {
+    let file_name = format!("{key}.webp");
+    for folder in ANIME_IMAGE_FOLDERS {
+        remove_cached_file(&cache_folder.join(folder).join(&file_name));
+    }
+    delete_transcoded_images(key, cache_folder);
+}
+
+fn move_cached_file(from: &Path, to: &Path) {
+    if let Some(parent) = to.parent() {
+        if let Err(e) = std::fs::create_dir_all(parent) {
+            warn!("Could not create folder `{}`: {e:?}", parent.display());
+            return;
+        }
+    }
+    match std::fs::rename(from, to) {
+        Ok(()) => {},
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {},
+        Err(e) => warn!("Could not move cached image `{}`: {e:?}", from.display())
+    }
+}
+
+/// Moves the stored variants of a poster into `backup_folder` with the same layout, so that
+/// [`restore_cached_images`] can put them back. The transcoded copies are removed, they are
+/// generated again on demand.
+pub fn backup_cached_images(key: &str, cache_folder: &Path, backup_folder: &Path) {
+    let file_name = format!("{key}.webp");
+    for folder in ANIME_IMAGE_FOLDERS {
+        move_cached_file(&cache_folder.join(folder).join(&file_name), &backup_folder.join(folder).join(&file_name));
+    }
+    delete_transcoded_images(key, cache_folder);
+}
+
+/// Puts back the variants saved by [`backup_cached_images`], missing ones are ignored
+pub fn restore_cached_images(key: &str, cache_folder: &Path, backup_folder: &Path) {
+    let file_name = format!("{key}.webp");
+    for folder in ANIME_IMAGE_FOLDERS {
+        move_cached_file(&backup_folder.join(folder).join(&file_name), &cache_folder.join(folder).join(&file_name));
+    }
+}
+
+/// A generated image whose poster key is not referenced anymore
+#[derive(Debug)]
+pub struct OrphanedImage {
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+/// Scans every image folder for files named after a key missing from `referenced`. Files
+/// modified during the last `grace` are skipped, they may belong to an anime being created.
+pub fn find_orphaned_images(referenced: &HashSet<String>, cache_folder: &Path, grace: Duration)
+    -> Result<(usize, Vec<OrphanedImage>)> {
+    let folders = ANIME_IMAGE_FOLDERS.iter().map(|folder| cache_folder.join(folder))
+        .chain(ANIME_POSTER_SIZES.iter().map(|size| cache_folder.join(ANIME_TRANSCODED_FOLDER).join(size)));
+    let mut scanned = 0;
+    let mut orphaned = Vec::new();
+    for folder in folders {
+        let entries = match std::fs::read_dir(&folder) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into())
+        };
+        for entry in entries {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if !metadata.is_file() {
+                continue;
+            }
+            let path = entry.path();
+            // partial transcodes and unrelated files are left alone
+            let key = match (path.file_stem().and_then(|s| s.to_str()),
+                path.extension().and_then(|e| e.to_str()).and_then(PosterFormat::from_extension)) {
+                (Some(key), Some(_)) => key,
+                _ => continue
+            };
+            scanned += 1;
+            let age = metadata.modified().ok().and_then(|modified| modified.elapsed().ok());
+            let old_enough = matches!(age, Some(age) if age >= grace);
+            if old_enough && !referenced.contains(key) {
+                orphaned.push(OrphanedImage { size: metadata.len(), path });
+            }
+        }
+    }
+    Ok((scanned, orphaned))
+}
+
+/// Reads the poster of the given size in the requested format, transcoding it the first time.
+/// Returns `None` if there is no such poster, `size` must be one of [`ANIME_POSTER_SIZES`].
+pub fn load_poster(key: &str, size: &str, format: PosterFormat, cache_folder: &Path) -> Result<Option<Vec<u8>>> {
+    let source = cache_folder.join(size).join(format!("{key}.webp"));
+    let path = match format {
+        PosterFormat::WebP => source.clone(),
+        _ => get_transcoded_path(key, size, format, cache_folder)
+    };
+    match std::fs::read(&path) {
+        Ok(image) => return Ok(Some(image)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && format != PosterFormat::WebP => {},
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into())
+    }
+
+    let file = match File::open(&source) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into())
+    };
     let t = Instant::now();
-    let file_name: String = format!("{cache_key}.webp");
-    let mut image: Image<Rgb> = Image::from_reader(ImageFormat::WebP, BufReader::new(File::open(from)?))
+    let image: Image<Rgb> = Image::from_reader(ImageFormat::WebP, BufReader::new(file))
+        .map_err(|e| anyhow!("Unable to open poster: {e:?}"))?;
+    let mut output = Vec::new();
+    let encoding = match format {
+        PosterFormat::WebP => ImageFormat::WebP,
+        PosterFormat::Png => ImageFormat::Png,
+        PosterFormat::Jpeg => ImageFormat::Jpeg,
+    };
+    image.encode(encoding, &mut output)
+        .map_err(|e| anyhow!("Unable to transcode poster: {e:?}"))?;
+
+    // written aside first so that a concurrent request never reads a partial file
+    std::fs::create_dir_all(path.parent().expect("Transcoded images are always in a folder"))?;
+    let partial = path.with_extension(format!("{}.partial", format.extension()));
+    std::fs::write(&partial, &output)?;
+    std::fs::rename(&partial, &path)?;
+    info!("Transcoded poster `{key}` to {} in {:?}", format.extension(), t.elapsed());
+    Ok(Some(output))
+}
+
+/// Renders the fallback poster at the given size in the requested format, it is not cached as
+/// it is only served for the few animes without a poster
+pub fn load_fallback_poster(fallback: &Path, size: &str, format: PosterFormat,
+    algorithm: ResizeAlgorithm) -> Result<Vec<u8>> {
+    let mut image: Image<Rgb> = Image::open(fallback)
+        .map_err(|e| anyhow!("Unable to open fallback poster: {e:?}"))?;
+    if size == ANIME_POSTER_MEDIUM_FOLDER {
+        image.resize(ANIME_POSTER_MEDIUM_WIDTH, ANIME_POSTER_MEDIUM_HEIGHT, algorithm);
+    }
+    let encoding = match format {
+        PosterFormat::WebP => ImageFormat::WebP,
+        PosterFormat::Png => ImageFormat::Png,
+        PosterFormat::Jpeg => ImageFormat::Jpeg,
+    };
+    let mut output = Vec::new();
+    image.encode(encoding, &mut output)
+        .map_err(|e| anyhow!("Unable to encode fallback poster: {e:?}"))?;
+    Ok(output)
+}
+
+fn save_webp(image: &Image<Rgb>, options: WebPEncoderOptions, output: &Path) -> Result<()> {
+    let dest = BufWriter::new(File::create(output)?);
+    let mut encoder = WebPStaticEncoder::new(dest, EncoderMetadata::from(image).with_config(options))?;
+    encoder.add_frame(image)?;
+    encoder.finish()?;
+    Ok(())
+}
+
+/// Returned by [`export_poster`] when the uploaded file holds more than one frame
+#[derive(Debug)]
+pub struct AnimatedImageError;
+
+impl std::fmt::Display for AnimatedImageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Animated images are not supported")
+    }
+}
+
+impl std::error::Error for AnimatedImageError {}
+
+/// Decodes the first frame of the uploaded file, frames are decoded lazily so at most two are
+/// read to tell an animated image apart.
+fn read_single_frame(from: &Path, format: ImageFormat) -> Result<Image<Rgb>> {
+    let mut frames = ImageSequence::<Rgb>::from_reader(format, BufReader::new(File::open(from)?))
         .map_err(|e| anyhow!("Unable to open uploaded file: {e:?}"))?;
+    let image = frames.next()
+        .ok_or_else(|| anyhow!("Uploaded file has no frame"))?
+        .map_err(|e| anyhow!("Unable to decode uploaded file: {e:?}"))?
+        .into_image();
+    if frames.next().is_some() {
+        return Err(AnimatedImageError.into());
+    }
+    Ok(image)
+}
+
+pub fn export_poster(cache_key: String, from: &Path, format: ImageFormat, cache_folder: &Path,
+    images: &ImagesConfig) -> Result<CachedImage> {
+    let t = Instant::now();
+    let file_name: String = format!("{cache_key}.webp");
+    let mut image = read_single_frame(from, format)?;
+
+    // the key is kept when the poster is replaced
+    delete_transcoded_images(&cache_key, cache_folder);
 
     // original poster
     let output = cache_folder.join(ANIME_POSTER_FULLRES_FOLDER).join(file_name.clone());
-    WebPEncoder::new()
-        .with_quality(100.)
-        .with_lossless(true)
-        .encode(&image, &mut BufWriter::new(File::create(output)?))
+    save_webp(&image, WebPEncoderOptions::new().with_quality(100.).with_lossless(true), &output)
         .map_err(|e| anyhow!("Unable to save original image: {e:?}"))?;
 
     // small poster
-    image.resize(ANIME_POSTER_MEDIUM_WIDTH, ANIME_POSTER_MEDIUM_HEIGHT, ResizeAlgorithm::Lanczos3);
+    image.resize(ANIME_POSTER_MEDIUM_WIDTH, ANIME_POSTER_MEDIUM_HEIGHT, images.resize_algorithm.into());
     let output = cache_folder.join(ANIME_POSTER_MEDIUM_FOLDER).join(file_name);
-    WebPEncoder::new()
-        .with_quality(ANIME_POSTER_MEDIUM_QUALITY)
-        .encode(&image, &mut BufWriter::new(File::create(output)?))
+    save_webp(&image, WebPEncoderOptions::new().with_quality(ANIME_POSTER_MEDIUM_QUALITY), &output)
         .map_err(|e| anyhow!("Unable to save resized image: {e:?}"))?;
 
+    info!("Successfully generated poster images in {:?}", t.elapsed());
+    Ok(compute_placeholder(cache_key, image, images))
+}
+
+/// Computes the blurhash placeholder, followed by the dominant color when it can be found, of the
+/// medium sized poster
+fn compute_placeholder(cache_key: String, image: Image<Rgb>, images: &ImagesConfig) -> CachedImage {
+    let components = images.placeholder_components();
     let mut placeholder = compute_dct_iter(image.data.iter().map(|p| [p.r, p.g, p.b]),
         image.width() as usize, image.height() as usize,
-        ANIME_PLACEHOLDER_COMPONENTS_X, ANIME_PLACEHOLDER_COMPONENTS_Y)
+        components.0, components.1)
         .into_blurhash();
 
     let pixels: Vec<u8> = image.data.into_iter().flat_map(|p| [p.r, p.g, p.b]).collect();
+    let mut accent = None;
     if let Ok(palette) = color_thief::get_palette(&pixels, color_thief::ColorFormat::Rgb, 10, 5) {
         placeholder.reserve(5);
         placeholder.push('/');
         let dominant = palette[2];
         let color = ((dominant.r as u32) << 16) | ((dominant.g as u32) << 8) | (dominant.b as u32);
         base83::encode_fixed_to(color, 4, &mut placeholder);
+        accent = Some(color);
     }
 
-    info!("Successfully generated poster images in {:?}", t.elapsed());
-    Ok(CachedImage::with_placeholder(cache_key, placeholder))
+    let poster = CachedImage::with_placeholder(cache_key, placeholder);
+    match accent {
+        Some(accent) => poster.with_accent(accent),
+        None => poster
+    }
+}
+
+/// Computes the placeholder of an already exported poster from its full resolution image,
+/// returns `None` if there is no such image
+pub fn recompute_placeholder(cache_key: String, cache_folder: &Path, images: &ImagesConfig)
+    -> Result<Option<CachedImage>> {
+    let from = cache_folder.join(ANIME_POSTER_FULLRES_FOLDER).join(format!("{cache_key}.webp"));
+    let file = match File::open(from) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into())
+    };
+    let mut image: Image<Rgb> = Image::from_reader(ImageFormat::WebP, BufReader::new(file))
+        .map_err(|e| anyhow!("Unable to open poster: {e:?}"))?;
+    // the same size as when the poster was exported
+    image.resize(ANIME_POSTER_MEDIUM_WIDTH, ANIME_POSTER_MEDIUM_HEIGHT, images.resize_algorithm.into());
+    Ok(Some(compute_placeholder(cache_key, image, images)))
 }
 
+/// Decodes the blurhash part of a poster placeholder into a `width`x`height` PNG
+pub fn render_placeholder_preview(placeholder: &str, width: u32, height: u32) -> Result<Vec<u8>> {
+    let blurhash = placeholder.split_once('/').map_or(placeholder, |(left, _)| left);
+    let dct = fast_blurhash::decode(blurhash, 1.)
+        .map_err(|e| anyhow!("Unable to decode placeholder: {e:?}"))?;
+    let pixels: Vec<Rgb> = dct.to_rgb8(width as usize, height as usize)
+        .into_iter()
+        .map(|[r, g, b]| Rgb::new(r, g, b))
+        .collect();
+
+    let mut output = Vec::new();
+    Image::from_pixels(width, pixels)
+        .encode(ImageFormat::Png, &mut output)
+        .map_err(|e| anyhow!("Unable to encode preview: {e:?}"))?;
+    Ok(output)
+}
+
+fn to_rgb(color: u32) -> Rgb {
+    Rgb::new((color >> 16) as u8, (color >> 8) as u8, color as u8)
+}
+
+/// Only used for posters exported before the accent color was stored on [`CachedImage`]
 fn get_dominant_color(blurhash: &str) -> Option<Rgb> {
     use base83::decode;
     let color = match blurhash.split_once('/') {
-        Some((_, right)) => decode(&right[..4]).ok()?,
-        _ => decode(&blurhash[2..6]).ok()?
+        Some((_, right)) => decode(right.get(..4)?).ok()?,
+        _ => decode(blurhash.get(2..6)?).ok()?
     };
-    Some(Rgb::new((color >> 16) as u8, (color >> 8) as u8, color as u8))
+    Some(to_rgb(color))
 }
 
 fn fit_and_draw_title(image: &mut ril::Image<ril::Rgb>, pos: (u32, u32),
@@ -83,7 +406,7 @@ fn fit_and_draw_title(image: &mut ril::Image<ril::Rgb>, pos: (u32, u32),
     if text.len() > 585 {
         text = &text[..585];
     }
-    let mut segment = TextSegment::new(&font, text, Rgb::white()).with_size(size);
+    let mut segment = TextSegment::new(font, text, Rgb::white()).with_size(size);
 
     loop {
         segment.size = size;
@@ -104,14 +427,24 @@ fn fit_and_draw_title(image: &mut ril::Image<ril::Rgb>, pos: (u32, u32),
     Ok(())
 }
 
-pub fn export_presenter<T: AsRef<AnimeSeries>>(recipient: T, cache_folder: &Path) -> Result<()> {
+fn presenter_title(recipient: &AnimeSeries) -> &str {
+    recipient.primary_title().unwrap_or(ANIME_PRESENTER_UNTITLED)
+}
+
+/// Renders the presenter of the anime, `fallback_poster` is pasted instead of the poster if the
+/// anime has none
+pub fn export_presenter<T: AsRef<AnimeSeries>>(recipient: T, cache_folder: &Path,
+    algorithm: ResizeAlgorithm, fallback_poster: Option<&Path>) -> Result<()> {
     let t = Instant::now();
     let recipient: &AnimeSeries = recipient.as_ref();
     let file_name: String = format!("{}.webp", recipient.poster.key());
-    let avg_color = match recipient.poster.placeholder().map(get_dominant_color) {
-        Some(Some(color)) => color,
-        _ => ACCENT_COLOR
-    };
+    let avg_color = recipient.accent_color
+        .or_else(|| recipient.poster.accent())
+        .map(to_rgb)
+        .or_else(|| recipient.poster.placeholder().and_then(get_dominant_color))
+        .unwrap_or(ACCENT_COLOR);
+
+    let layout = PresenterLayout::load(Path::new(ANIME_PRESENTER_LAYOUT))?;
 
     let (mut presenter, poster_width) = {
         let input = BufReader::new(File::open(ANIME_PRESENTER_TEMPLATE)?);
@@ -119,72 +452,90 @@ pub fn export_presenter<T: AsRef<AnimeSeries>>(recipient: T, cache_folder: &Path
             .map_err(|e| anyhow!("Unable to open template image: {e:?}"))?;
 
         let from = cache_folder.join(ANIME_POSTER_FULLRES_FOLDER).join(file_name.clone());
-        let input = BufReader::new(File::open(from)?);
-        let mut poster: Image<Rgb> = Image::from_reader(ImageFormat::WebP, input)
+        let poster = match (File::open(from), fallback_poster) {
+            (Ok(file), _) => Image::from_reader(ImageFormat::WebP, BufReader::new(file)),
+            (Err(e), Some(fallback)) if e.kind() == std::io::ErrorKind::NotFound => Image::open(fallback),
+            (Err(e), _) => return Err(e.into())
+        };
+        let mut poster: Image<Rgb> = poster
             .map_err(|e| anyhow!("Unable to open uploaded file: {e:?}"))?;
 
         let poster_width = ANIME_POSTER_MEDIUM_WIDTH * template.height() / ANIME_POSTER_MEDIUM_HEIGHT;
-        poster.resize(poster_width, template.height(), ResizeAlgorithm::Lanczos3);
+        poster.resize(poster_width, template.height(), algorithm);
         template.paste(0, 0, &poster);
 
         (template, poster_width)
     };
 
     { // render title
-        const TITLE_BASE_FONT_SIZE: f32 = 64.;
-        let xbold = Font::open("assets/fonts/Poppins-ExtraBold.ttf", TITLE_BASE_FONT_SIZE)
+        let title = layout.title;
+        let xbold = Font::open(ANIME_PRESENTER_FONT, title.font_size)
             .map_err(|e| anyhow!("Unable to open font file: {e:?}"))?;
 
-        let w = presenter.width() - poster_width - 64;
-        fit_and_draw_title(&mut presenter, (452, 82), w, 212,
-            &xbold, &recipient.titles[0], TITLE_BASE_FONT_SIZE)?;
+        let w = presenter.width() - poster_width - title.right_margin;
+        fit_and_draw_title(&mut presenter, (title.position.x, title.position.y), w, title.max_height,
+            &xbold, presenter_title(recipient), title.font_size)?;
     }
 
-    let bold_buf = std::fs::read("assets/fonts/Poppins-ExtraBold.ttf")
+    let bold_buf = std::fs::read(ANIME_PRESENTER_FONT)
         .map_err(|e| anyhow!("Unable to open font file: {e:?}"))?;
 
-    let bold = Font::from_bytes(&bold_buf, 28.0)
+    let bold = Font::from_bytes(&bold_buf, layout.year.font_size)
         .map_err(|e| anyhow!("Unable to open font file: {e:?}"))?;
 
     presenter.draw(&TextLayout::new() // year
         .centered()
-        .with_position(452 + 64, 32 + 21 + 2)
+        .with_position(layout.year.position.x, layout.year.position.y)
         .with_basic_text(&bold, recipient.anime.release_year.to_string(), ACCENT_COLOR));
 
-    let bold = Font::from_bytes(&bold_buf, 32.0)
+    let bold = Font::from_bytes(&bold_buf, layout.stats_font_size)
         .map_err(|e| anyhow!("Unable to open font file: {e:?}"))?;
 
-    presenter.draw(&TextLayout::new() // episode count
-        .with_position(532, 534 + 32 + 4)
-        .with_vertical_anchor(VerticalAnchor::Center)
-        .with_basic_text(&bold, recipient.anime.episodes.to_string(), avg_color)
-        .with_basic_text(&bold, " episodes", Rgb::white()));
-
-    presenter.draw(&TextLayout::new() // season count
-        .with_position(532, 454 + 32 + 4)
-        .with_vertical_anchor(VerticalAnchor::Center)
-        .with_basic_text(&bold, recipient.anime.seasons.to_string(), avg_color)
-        .with_basic_text(&bold, " seasons", Rgb::white()));
-
-    presenter.draw(&TextLayout::new() // chapter count
-        .with_position(532, 374 + 32 + 4)
-        .with_vertical_anchor(VerticalAnchor::Center)
-        .with_basic_text(&bold, recipient.manga.chapters.to_string(), avg_color)
-        .with_basic_text(&bold, " chapters", Rgb::white()));
-
-    presenter.draw(&TextLayout::new() // volume count
-        .with_position(532, 294 + 32 + 4)
-        .with_vertical_anchor(VerticalAnchor::Center)
-        .with_basic_text(&bold, recipient.manga.volumes.to_string(), avg_color)
-        .with_basic_text(&bold, " volumes", Rgb::white()));
+    let stats = [
+        (layout.episodes, recipient.anime.episodes, " episodes"),
+        (layout.seasons, recipient.anime.seasons, " seasons"),
+        (layout.chapters, recipient.manga.chapters, " chapters"),
+        (layout.volumes, recipient.manga.volumes, " volumes"),
+    ];
+    for (position, value, label) in stats {
+        presenter.draw(&TextLayout::new()
+            .with_position(position.x, position.y)
+            .with_vertical_anchor(VerticalAnchor::Center)
+            .with_basic_text(&bold, value.to_string(), avg_color)
+            .with_basic_text(&bold, label, Rgb::white()));
+    }
 
     let output = cache_folder.join(ANIME_PRESENTER_FOLDER).join(file_name);
-    WebPEncoder::new()
-        .with_quality(100.)
-        .with_lossless(true)
-        .encode(&presenter, &mut BufWriter::new(File::create(output)?))
+    save_webp(&presenter, WebPEncoderOptions::new().with_quality(100.).with_lossless(true), &output)
         .map_err(|e| anyhow!("Unable to save presenter image: {e:?}"))?;
 
     info!("Successfully generated presenter image in {:?}", t.elapsed());
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::get_anime;
+
+    #[test]
+    fn renders_presenter_without_titles() {
+        let cache_folder = std::env::temp_dir().join(format!("kanime-presenter-{}", std::process::id()));
+        std::fs::create_dir_all(cache_folder.join(ANIME_PRESENTER_FOLDER)).unwrap();
+        let fallback = cache_folder.join("fallback.png");
+        Image::new(ANIME_POSTER_MEDIUM_WIDTH, ANIME_POSTER_MEDIUM_HEIGHT, Rgb::black())
+            .save(ImageFormat::Png, &fallback)
+            .unwrap();
+
+        let mut anime = get_anime();
+        anime.titles.clear();
+        assert_eq!(presenter_title(&anime), ANIME_PRESENTER_UNTITLED);
+
+        let rendered = export_presenter(&anime, &cache_folder, ResizeAlgorithm::Nearest, Some(&fallback));
+        let presenter = get_presenter_path(anime.poster.key(), &cache_folder);
+        let exists = presenter.exists();
+        std::fs::remove_dir_all(&cache_folder).unwrap();
+        rendered.unwrap();
+        assert!(exists);
+    }
+}
@@ -0,0 +1,76 @@
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+use std::path::Path;
+
+/// Layout file read next to the presenter template, the built-in layout is used when missing
+pub const ANIME_PRESENTER_LAYOUT: &str = "assets/templates/AnimePresenter.json";
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct Position {
+    pub x: u32,
+    pub y: u32,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct TitleLayout {
+    pub position: Position,
+    /// Space kept between the title and the right edge of the template
+    pub right_margin: u32,
+    pub max_height: u32,
+    pub font_size: f32,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct YearLayout {
+    /// Center of the year text
+    pub position: Position,
+    pub font_size: f32,
+}
+
+/// Positions are in pixels of the template, stat rows are anchored on their vertical center
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PresenterLayout {
+    pub title: TitleLayout,
+    pub year: YearLayout,
+    pub stats_font_size: f32,
+    pub episodes: Position,
+    pub seasons: Position,
+    pub chapters: Position,
+    pub volumes: Position,
+}
+
+impl Default for PresenterLayout {
+    fn default() -> Self {
+        Self {
+            title: TitleLayout {
+                position: Position { x: 452, y: 82 },
+                right_margin: 64,
+                max_height: 212,
+                font_size: 64.,
+            },
+            year: YearLayout {
+                position: Position { x: 452 + 64, y: 32 + 21 + 2 },
+                font_size: 28.,
+            },
+            stats_font_size: 32.,
+            episodes: Position { x: 532, y: 534 + 32 + 4 },
+            seasons: Position { x: 532, y: 454 + 32 + 4 },
+            chapters: Position { x: 532, y: 374 + 32 + 4 },
+            volumes: Position { x: 532, y: 294 + 32 + 4 },
+        }
+    }
+}
+
+impl PresenterLayout {
+    pub fn load(path: &Path) -> Result<Self> {
+        match std::fs::read(path) {
+            Ok(raw) => serde_json::from_slice(&raw)
+                .map_err(|e| anyhow!("Invalid presenter layout `{}`: {e}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(anyhow!("Unable to read presenter layout: {e:?}"))
+        }
+    }
+}
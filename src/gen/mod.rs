@@ -1 +1,28 @@
+use std::sync::Arc;
+use actix_web::web;
+use anyhow::Result;
+use tokio::sync::Semaphore;
+
 pub mod anime;
+pub mod layout;
+
+/// Bounds the number of images generated at once across every worker, the jobs over the limit
+/// wait for their turn instead of competing for the CPU
+#[derive(Clone)]
+pub struct ImageJobs(Arc<Semaphore>);
+
+impl ImageJobs {
+    pub fn new(max_concurrent: usize) -> Self {
+        Self(Arc::new(Semaphore::new(max_concurrent)))
+    }
+
+    /// Waits for a free slot and runs `job` on the blocking pool
+    pub async fn run<F, R>(&self, job: F) -> Result<R>
+        where
+            F: FnOnce() -> Result<R> + Send + 'static,
+            R: Send + 'static,
+    {
+        let _permit = self.0.acquire().await.expect("The semaphore is never closed");
+        web::block(job).await?
+    }
+}
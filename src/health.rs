@@ -0,0 +1,59 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use log::{info, warn};
+
+/// Last known state of Meilisearch, shared by every worker. It is only refreshed when the
+/// periodic health check is enabled, otherwise it keeps the state seen at startup.
+#[derive(Clone)]
+pub struct MeilisearchHealth {
+    healthy: Arc<AtomicBool>,
+    monitored: bool,
+}
+
+impl MeilisearchHealth {
+    pub fn new(healthy: bool, monitored: bool) -> Self {
+        Self { healthy: Arc::new(AtomicBool::new(healthy)), monitored }
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+
+    /// Whether Meilisearch was down at the last check, never true without periodic checks as
+    /// nothing would notice it coming back up
+    pub fn is_known_down(&self) -> bool {
+        self.monitored && !self.is_healthy()
+    }
+
+    /// Returns the previous state
+    fn set(&self, healthy: bool) -> bool {
+        self.healthy.swap(healthy, Ordering::Relaxed)
+    }
+}
+
+/// Checks Meilisearch every `interval` and runs `on_recovery` each time it comes back up, runs
+/// until the process exits
+pub async fn watch_meilisearch<F, Fut>(health: MeilisearchHealth, meilisearch: meilisearch_sdk::Client,
+                                       interval: Duration, on_recovery: F)
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output=()>,
+{
+    let mut ticks = actix_web::rt::time::interval(interval);
+    // the first tick completes immediately, the state at startup is already known
+    ticks.tick().await;
+    loop {
+        ticks.tick().await;
+        let healthy = meilisearch.is_healthy().await;
+        match (health.set(healthy), healthy) {
+            (false, true) => {
+                info!(target: "meilisearch", "Back online");
+                on_recovery().await;
+            },
+            (true, false) => warn!(target: "meilisearch", "No signs of life, searching through MongoDB"),
+            _ => {}
+        }
+    }
+}
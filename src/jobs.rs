@@ -0,0 +1,173 @@
+use std::cell::RefCell;
+use std::future::Future;
+use std::rc::Rc;
+use std::time::SystemTime;
+use anyhow::Result;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use log::{error, info, warn};
+
+use crate::pool::RedisPool;
+
+const JOB_REDIS_KEY_PREFIX: &str = "job";
+// sorted set of the job ids by start time, entries older than the TTL are pruned when listing
+const JOBS_REDIS_INDEX: &str = "jobs";
+const JOB_TTL_SECS: usize = 24 * 60 * 60; // 1 day
+const JOB_ID_ALPHABET: &str = "ABCDEFGHIJKMNOPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz0123456789";
+const JOB_ID_LENGTH: usize = 16;
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .expect("The time can never be earlier than the Unix epoch")
+        .as_millis() as u64
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum JobStatus {
+    Running,
+    Completed,
+    Failed,
+}
+
+/// State of a background job as stored in Redis, it expires a day after its last update
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct JobHandle {
+    pub id: String,
+    pub kind: String,
+    pub status: JobStatus,
+    pub progress: u64,
+    pub total: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub started_on: u64,
+    pub finished_on: Option<u64>,
+}
+
+impl JobHandle {
+    fn key(id: &str) -> String {
+        format!("{JOB_REDIS_KEY_PREFIX}:{id}")
+    }
+
+    pub async fn load(redis: &RedisPool, id: &str) -> Result<Option<Self>> {
+        let mut con = redis.get().await?;
+        let raw: Option<String> = con.get(Self::key(id)).await?;
+        Ok(match raw {
+            Some(raw) => Some(serde_json::from_str(&raw)?),
+            None => None
+        })
+    }
+
+    /// Returns the most recent jobs first, at most `limit` of them
+    pub async fn list(redis: &RedisPool, limit: usize) -> Result<Vec<Self>> {
+        if limit == 0 {
+            return Ok(vec![]);
+        }
+        let mut con = redis.get().await?;
+        let expired = now().saturating_sub(JOB_TTL_SECS as u64 * 1000);
+        con.zrembyscore::<_, _, _, ()>(JOBS_REDIS_INDEX, "-inf", expired).await?;
+        let ids: Vec<String> = con.zrevrange(JOBS_REDIS_INDEX, 0, limit as isize - 1).await?;
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+        let keys: Vec<String> = ids.iter().map(|id| Self::key(id)).collect();
+        let raw: Vec<Option<String>> = redis::cmd("MGET").arg(&keys).query_async(&mut con).await?;
+        raw.into_iter()
+            .flatten()
+            .map(|raw| Ok(serde_json::from_str(&raw)?))
+            .collect()
+    }
+
+    async fn register(&self, redis: &RedisPool) -> Result<()> {
+        self.save(redis).await?;
+        let mut con = redis.get().await?;
+        con.zadd::<_, _, _, ()>(JOBS_REDIS_INDEX, &self.id, self.started_on).await?;
+        Ok(())
+    }
+
+    async fn save(&self, redis: &RedisPool) -> Result<()> {
+        let mut con = redis.get().await?;
+        con.set_ex::<_, _, ()>(Self::key(&self.id), serde_json::to_string(self)?, JOB_TTL_SECS).await?;
+        Ok(())
+    }
+}
+
+/// Given to the job body to report how far it went, failures to save are only logged
+#[derive(Clone)]
+pub struct JobProgress {
+    handle: Rc<RefCell<JobHandle>>,
+    redis: RedisPool,
+}
+
+impl JobProgress {
+    pub async fn set_total(&self, total: u64) {
+        self.handle.borrow_mut().total = Some(total);
+        self.save().await;
+    }
+
+    pub async fn advance(&self) {
+        self.advance_by(1).await;
+    }
+
+    pub async fn advance_by(&self, steps: u64) {
+        self.handle.borrow_mut().progress += steps;
+        self.save().await;
+    }
+
+    async fn finish(&self, res: Result<()>) {
+        {
+            let mut handle = self.handle.borrow_mut();
+            handle.finished_on = Some(now());
+            match res {
+                Ok(()) => {
+                    handle.status = JobStatus::Completed;
+                    info!(target: "jobs", "Job `{}` completed", handle.id);
+                },
+                Err(e) => {
+                    error!(target: "jobs", "Job `{}` failed: {e:?}", handle.id);
+                    handle.status = JobStatus::Failed;
+                    handle.error = Some(format!("{e:#}"));
+                }
+            }
+        }
+        self.save().await;
+    }
+
+    async fn save(&self) {
+        // the borrow must not be held across the await, the job keeps running meanwhile
+        let handle = self.handle.borrow().clone();
+        if let Err(e) = handle.save(&self.redis).await {
+            warn!(target: "jobs", "Could not save progress of job `{}`: {e:?}", handle.id);
+        }
+    }
+}
+
+/// Registers a job of the given `kind` and runs `job` on the current worker without waiting for
+/// it, the returned handle is the initial state.
+pub async fn spawn_job<F, Fut>(redis: &RedisPool, kind: &str, job: F) -> Result<JobHandle>
+    where
+        F: FnOnce(JobProgress) -> Fut + 'static,
+        Fut: Future<Output=Result<()>> + 'static,
+{
+    let handle = JobHandle {
+        id: random_string::generate(JOB_ID_LENGTH, JOB_ID_ALPHABET),
+        kind: kind.to_string(),
+        status: JobStatus::Running,
+        progress: 0,
+        total: None,
+        error: None,
+        started_on: now(),
+        finished_on: None,
+    };
+    handle.register(redis).await?;
+    info!(target: "jobs", "Started {kind} job `{}`", handle.id);
+
+    let progress = JobProgress { handle: Rc::new(RefCell::new(handle.clone())), redis: redis.clone() };
+    actix_web::rt::spawn(async move {
+        let res = job(progress.clone()).await;
+        progress.finish(res).await;
+    });
+    Ok(handle)
+}
@@ -0,0 +1,46 @@
+use anyhow::Result;
+use log::warn;
+
+use crate::pool::RedisPool;
+
+const LOCK_REDIS_KEY_PREFIX: &str = "lock";
+const LOCK_TOKEN_ALPHABET: &str = "ABCDEFGHIJKMNOPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz0123456789";
+/// Deletes the key only if it still holds the token, both in one step so that a lock taken by
+/// someone else between the check and the deletion is left alone
+const LOCK_RELEASE_SCRIPT: &str = "if redis.call('get',KEYS[1])==ARGV[1] then return redis.call('del',KEYS[1]) end";
+
+/// A best-effort distributed lock held in Redis, it expires by itself after its TTL
+/// so a crashed holder can never block the others forever.
+pub struct RedisLock {
+    key: String,
+    token: String,
+}
+
+impl RedisLock {
+    /// Tries to take the lock with `SET NX PX`, returns `None` if it is already held.
+    pub async fn try_acquire(redis: &RedisPool, name: &str, ttl_ms: usize) -> Result<Option<Self>> {
+        let key = format!("{LOCK_REDIS_KEY_PREFIX}:{name}");
+        let token = random_string::generate(20, LOCK_TOKEN_ALPHABET);
+        let mut con = redis.get().await?;
+        let acquired: Option<String> = redis::cmd("SET")
+            .arg(&key).arg(&token)
+            .arg("NX").arg("PX").arg(ttl_ms)
+            .query_async(&mut con).await?;
+        Ok(acquired.map(|_| Self { key, token }))
+    }
+
+    /// Releases the lock if it is still ours, it may have expired and been taken by someone else.
+    pub async fn release(self, redis: &RedisPool) {
+        let res: Result<()> = async {
+            let mut con = redis.get().await?;
+            redis::cmd("EVAL")
+                .arg(LOCK_RELEASE_SCRIPT).arg(1)
+                .arg(&self.key).arg(&self.token)
+                .query_async::<_, ()>(&mut con).await?;
+            Ok(())
+        }.await;
+        if let Err(e) = res {
+            warn!(target: "redis", "Could not release lock `{}`: {e:?}", self.key);
+        }
+    }
+}
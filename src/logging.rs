@@ -0,0 +1,29 @@
+use std::io::Write;
+use env_logger::{Builder, Env};
+use serde_json::json;
+use crate::config::LogFormat;
+
+/// Sets up the global logger, `RUST_LOG` still controls the filter in both formats
+pub fn init(format: LogFormat) {
+    let mut builder = Builder::from_env(Env::default().default_filter_or("info"));
+    if format == LogFormat::Json {
+        builder.format(|buf, record| {
+            let line = json!({
+                "timestamp": buf.timestamp_millis().to_string(),
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": record.args().to_string()
+            });
+            writeln!(buf, "{line}")
+        });
+    }
+    builder.init();
+}
+
+/// Access log format of the actix `Logger`, in JSON mode the whole line ends up in `message`
+pub fn access_log_format(format: LogFormat) -> &'static str {
+    match format {
+        LogFormat::Text => "%a %r %{UID}xi [%{RID}xi] » %s ~%Dms",
+        LogFormat::Json => "%a %r %{UID}xi %{RID}xi %s %Dms",
+    }
+}
@@ -5,75 +5,174 @@ mod config;
 mod routes;
 mod middlewares;
 mod gen;
+mod lock;
+mod jobs;
+mod pool;
+mod tls;
+mod logging;
+mod session_cache;
+mod health;
 
 use config::*;
-use std::{fs, path::Path};
+use std::{fs, path::{Path, PathBuf}};
 use std::string::ToString;
+use std::time::Duration;
 use actix_web::{web, App, HttpServer, middleware, HttpRequest, HttpResponse, http::Method};
 use actix_web::middleware::{Condition, Logger};
 use serde_json::json;
-use env_logger::Env;
 use log::{error, info, warn};
-use mongodb::Client;
+use mongodb::{Client, options::ClientOptions};
 use gethostname::gethostname;
 
 use types::{AppState, KError};
+use lock::RedisLock;
+use pool::RedisPool;
 use middlewares::ip::CloudflareClientIp;
-use middlewares::auth::{KanimeAuth, pick_user_id};
+use middlewares::compress::TunedCompress;
+use middlewares::request_id::{RequestIdHeader, pick_request_id};
+use middlewares::timeout::RequestTimeout;
+use middlewares::auth::{KanimeAuth, pick_user_id, role_rejection_response};
 
 const MAJOR_VERSION: Option<&'static str> = option_env!("CARGO_PKG_VERSION_MAJOR");
 const MINOR_VERSION: Option<&'static str> = option_env!("CARGO_PKG_VERSION_MINOR");
 const PATCH_VERSION: Option<&'static str> = option_env!("CARGO_PKG_VERSION_PATCH");
 
-async fn default_endpoint(req: HttpRequest) -> HttpResponse {
+const CACHE_PROBE_FILE: &str = ".write-probe";
+
+/// Creates the cache folder and its image folders if needed and makes sure we can write to it,
+/// returns its absolute path
+fn check_cache_folder(cache_folder: &Path) -> std::io::Result<PathBuf> {
+    fs::create_dir_all(cache_folder)?;
+    for folder in gen::anime::ANIME_IMAGE_FOLDERS {
+        fs::create_dir_all(cache_folder.join(folder))?;
+    }
+    let probe = cache_folder.join(CACHE_PROBE_FILE);
+    fs::write(&probe, b"")?;
+    fs::remove_file(&probe)?;
+    cache_folder.canonicalize()
+}
+
+async fn default_endpoint(req: HttpRequest, app: web::Data<AppState>) -> HttpResponse {
     match req.method() {
         &Method::OPTIONS => HttpResponse::NoContent().finish(),
-        _ => KError::not_found()
+        _ => role_rejection_response(&req, &app).unwrap_or_else(KError::not_found)
+    }
+}
+
+/// Syncs the index unless another instance already is, as when several replicas start at once
+async fn run_auto_sync(mongodb: &Client, meilisearch: &meilisearch_sdk::Client, redis: &RedisPool,
+                       search: &SearchConfig) {
+    use routes::anime::{ANIMES_INDEX_LOCK, ANIMES_INDEX_LOCK_TTL_MS};
+    match RedisLock::try_acquire(redis, ANIMES_INDEX_LOCK, ANIMES_INDEX_LOCK_TTL_MS).await {
+        Ok(Some(lock)) => {
+            if let Err(e) = routes::anime::sync_meilisearch(mongodb, meilisearch, redis, search).await {
+                error!("Could not perform auto-sync: {e}");
+            }
+            lock.release(redis).await;
+        },
+        Ok(None) => info!(target: "meilisearch", "Sync already in progress on another instance, skipping"),
+        Err(e) => error!("Could not acquire sync lock: {e}")
     }
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    env_logger::init_from_env(Env::default().default_filter_or("info"));
-    info!("Reading config...");
-
     let raw_config = fs::read_to_string(CONFIG_FILE)?;
     let config: Config = toml::from_str(&raw_config)?;
+    let log_format = config.logging.format;
+    logging::init(log_format);
+    info!("Read config from `{CONFIG_FILE}`");
+    config.validate()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    let tls_config = match &config.http.tls {
+        Some(tls) => Some(tls::load_server_config(tls)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{e:#}")))?),
+        None => None
+    };
+    let cache_folder = check_cache_folder(Path::new(&config.cache_folder))
+        .map_err(|e| std::io::Error::new(e.kind(),
+            format!("Cache folder `{}` is not usable: {e}", config.cache_folder)))?;
+    info!("Using cache folder `{}`", cache_folder.display());
+    if config.images.presenter_enabled() {
+        let missing = gen::anime::missing_presenter_assets();
+        if !missing.is_empty() {
+            warn!("Presenters cannot be generated, missing assets: {}", missing.join(", "));
+        }
+    } else {
+        info!("Presenter generation is disabled");
+    }
     let addr: (String, u16) = config.http.clone().into();
     let name: String = gethostname().into_string()
         .unwrap_or_else(|_| "kanime-api-v3".to_string());
     info!("Starting server as `{name}`");
 
-    let mongodb = Client::with_uri_str(config.mongodb.with_client_name(&name))
-        .await.expect("Error: Failed to connect to MongoDB");
+    let mut mongodb_options = ClientOptions::parse(config.mongodb.with_client_name(&name))
+        .await.expect("Error: Invalid MongoDB configuration");
+    config.mongodb.apply_pool_options(&mut mongodb_options);
+    let mongodb = Client::with_options(mongodb_options)
+        .expect("Error: Failed to connect to MongoDB");
     info!(target: "mongodb", "MongoDB client setup done!");
+    if let Err(e) = routes::anime::ensure_indexes(&mongodb, &config.catalog).await {
+        error!(target: "mongodb", "Could not create indexes: {e:?}");
+    }
 
     let redis = redis::Client::open(config.redis.clone())
         .expect("Could not connect to redis");
     info!(target: "redis", "Redis client setup done!");
+    let startup_redis = RedisPool::new(redis.clone());
 
     let meilisearch: meilisearch_sdk::Client = config.meilisearch.as_client();
-    if meilisearch.is_healthy().await {
+    let auto_sync = config.meilisearch.auto_sync();
+    let healthy = meilisearch.is_healthy().await;
+    if healthy {
         info!(target: "meilisearch", "Successfully connected!");
-        if config.meilisearch.auto_sync.unwrap_or(true) {
-            if let Err(e) = routes::anime::sync_meilisearch(&mongodb, &meilisearch).await {
-                 error!("Could not perform auto-sync: {e}");
-            }
+        if auto_sync {
+            run_auto_sync(&mongodb, &meilisearch, &startup_redis, &config.search).await;
         }
     } else {
         warn!(target: "meilisearch", "No signs of life...");
     }
+    let health_check_interval = config.meilisearch.health_check_interval();
+    let meilisearch_health = health::MeilisearchHealth::new(healthy, health_check_interval.is_some());
+    if let Some(interval) = health_check_interval {
+        let (mongodb, meilisearch, redis, search) =
+            (mongodb.clone(), meilisearch.clone(), startup_redis.clone(), config.search.clone());
+        actix_web::rt::spawn(health::watch_meilisearch(meilisearch_health.clone(), meilisearch.clone(), interval,
+            move || {
+                let (mongodb, meilisearch, redis, search) =
+                    (mongodb.clone(), meilisearch.clone(), redis.clone(), search.clone());
+                async move {
+                    if auto_sync {
+                        run_auto_sync(&mongodb, &meilisearch, &redis, &search).await;
+                    }
+                }
+            }));
+    }
 
-    let cache_folder = Path::new(&config.cache_folder).to_path_buf();
+    let images = config.images.clone();
+    // shared by every worker so that the limit holds for the whole process
+    let image_jobs = gen::ImageJobs::new(images.max_concurrent_image_jobs());
+    let auth = config.auth.clone();
+    // shared so that a revoked session is dropped for every worker at once
+    let session_cache = session_cache::SessionCache::new(auth.session_cache_ttl(), auth.session_cache_size());
+    let cache = config.cache.clone();
+    let search = config.search.clone();
+    let compression = config.compression;
+    let catalog = config.catalog.clone();
+    let list = config.list.clone();
+    let request_timeout = config.http.request_timeout();
+    let meilisearch_timeout = config.meilisearch.timeout();
 
-    info!(target: "http", "Listening on {}:{}", addr.0, addr.1);
     let debug = config.debug.unwrap_or(false);
     let domain = config.domain.to_string();
-    HttpServer::new(move || {
+    let scheme = config.scheme.clone();
+    let app_factory = move || {
         App::new()
             .app_data(web::Data::new(AppState {
                 app_name: name.clone(),
+                debug,
                 domain: domain.clone(),
+                scheme: scheme.clone(),
                 version_info: json!({
                     "major": MAJOR_VERSION.unwrap_or("3"),
                     "minor": MINOR_VERSION.unwrap_or("0"),
@@ -81,23 +180,58 @@ async fn main() -> std::io::Result<()> {
                 }).to_string(),
                 mongodb: mongodb.clone(),
                 meilisearch: meilisearch.clone(),
-                redis: redis.clone(),
-                cache_folder: cache_folder.clone()
+                meilisearch_timeout,
+                meilisearch_health: meilisearch_health.clone(),
+                // one connection per worker, it is driven by the runtime that opened it
+                redis: RedisPool::new(redis.clone()),
+                cache_folder: cache_folder.clone(),
+                images: images.clone(),
+                image_jobs: image_jobs.clone(),
+                auth: auth.clone(),
+                session_cache: session_cache.clone(),
+                cache: cache.clone(),
+                search: search.clone(),
+                catalog: catalog.clone(),
+                list: list.clone()
             }))
-            .wrap(Logger::new("%a %r %{UID}xi » %s ~%Dms")
+            .app_data(routes::anime::multipart_form_config(images.max_poster_bytes()))
+            .wrap(Logger::new(logging::access_log_format(log_format))
                 .custom_request_replace("UID", pick_user_id)
+                .custom_request_replace("RID", pick_request_id)
                 .log_target("http"))
+            .wrap(Condition::new(compression.is_tuned(), TunedCompress(compression)))
             .wrap(middleware::Compress::default())
             .wrap(Condition::new(!debug, CloudflareClientIp))
             .wrap(KanimeAuth)
+            .wrap(RequestTimeout::new(request_timeout).exempting(&routes::UNTIMED_PATHS))
             .wrap(middleware::DefaultHeaders::new()
                 .add(("Access-Control-Allow-Origin", "*"))
-                .add(("Access-Control-Allow-Headers", "Content-Type, Accept"))
-                .add(("Access-Control-Allow-Methods", "GET, POST, OPTIONS")))
+                .add(("Access-Control-Allow-Headers", "Content-Type, Accept, Range, X-Request-Id"))
+                .add(("Access-Control-Allow-Methods", "GET, POST, OPTIONS"))
+                .add(("Access-Control-Expose-Headers", "Location, X-Next-Offset, X-Has-More, X-Search-Degraded, X-Request-Id, \
+                    X-Estimated-Total-Hits, X-Offset-Out-Of-Range, Content-Range, Accept-Ranges")))
+            // outermost so that every response carries the id, even the ones answered above
+            .wrap(RequestIdHeader)
             .default_service(web::to(default_endpoint))
-            .configure(routes::configure)
-    })
-    .bind(addr)?
-    .run()
-    .await
+            .configure(|cfg| routes::configure(cfg, &auth))
+    };
+
+    let workers = config.http.workers;
+    let keep_alive = config.http.keep_alive_secs.map(Duration::from_secs);
+    if let Some(tls_config) = tls_config {
+        info!(target: "http", "Listening on {}:{} (TLS)", addr.0, addr.1);
+        return tls::bind_rustls(addr, tls_config, app_factory, workers, keep_alive)?.await;
+    }
+
+    info!(target: "http", "Listening on {}:{}", addr.0, addr.1);
+    let mut server = HttpServer::new(app_factory);
+    if let Some(workers) = workers {
+        server = server.workers(workers);
+    }
+    if let Some(keep_alive) = keep_alive {
+        server = server.keep_alive(keep_alive);
+    }
+    server.bind(addr)?
+        .run()
+        .await
 }
@@ -4,10 +4,10 @@ use std::rc::Rc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use actix_web::{
-    HttpMessage, HttpResponse, Error, web,
+    HttpMessage, HttpRequest, HttpResponse, Error, web,
     body::EitherBody,
     guard::{Guard, GuardContext},
-    http::{header::HeaderValue, StatusCode},
+    http::{header::HeaderValue, Method, StatusCode},
     dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
 };
 
@@ -15,14 +15,12 @@ use redis::AsyncCommands;
 use anyhow::{anyhow, Result};
 use log::warn;
 use serde::{Deserialize, Serialize};
-use crate::types::AppState;
+use crate::types::{AppState, KError};
 
-const TOKEN_REDIS_KEY_PREFIX: &str = "tk";
 const AUTHORIZATION_HEADER: &str = "Authorization";
 const TOKEN_BASE_TYPE: &str = "Bearer";
-const TOKEN_LENGTH: u8 = 42;
 
-const NANOID_ALPHABET: [char; 64] = [
+pub(crate) const NANOID_ALPHABET: [char; 64] = [
     '_', '-', '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', 'a', 'b', 'c', 'd', 'e', 'f', 'g',
     'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z',
     'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O', 'P', 'Q', 'R', 'S',
@@ -30,18 +28,27 @@ const NANOID_ALPHABET: [char; 64] = [
 ];
 
 pub fn pick_user_id(req: &ServiceRequest) -> String {
-    if let Some(ses) = req.extensions().get::<Session>() {
-        format!("<{:?}@{}>", ses.role, ses.user_id)
-    } else {
-        "<A>".to_string()
+    let exts = req.extensions();
+    match (exts.get::<AuthContext>(), exts.get::<Session>()) {
+        (Some(AuthContext { role: Some(Role::Admin), .. }), Some(ses)) =>
+            format!("<{:?}@{}>", ses.role, ses.user_id),
+        (Some(AuthContext { authenticated: true, .. }), Some(ses)) =>
+            format!("<U@{}>", ses.user_id),
+        _ => "<A>".to_string()
     }
 }
 
+pub fn generate_token(length: u8) -> String {
+    let alphabet: String = NANOID_ALPHABET.iter().collect();
+    random_string::generate(length as usize, &alphabet)
+}
+
 fn validate_nanoid(str: &str, expected_len: u8) -> bool {
     str.len() == expected_len as usize && str.chars().all(|c| NANOID_ALPHABET.contains(&c))
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/// Roles are ordered by privilege, `User < Mod < Admin`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum Role {
     User,
@@ -57,8 +64,35 @@ pub struct Session {
     pub role: Role
 }
 
+/// Always present in the request extensions once the request went through [`KanimeAuth`],
+/// whether or not a session was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuthContext {
+    pub authenticated: bool,
+    pub role: Option<Role>
+}
+
+impl AuthContext {
+    pub fn anonymous() -> Self {
+        Self { authenticated: false, role: None }
+    }
+
+    pub fn from_session(session: &Session) -> Self {
+        Self { authenticated: true, role: Some(session.role) }
+    }
+}
+
 pub struct KanimeAuth;
 
+/// Preflight requests and `/s/` routes are left alone, the latter are guarded by role already, as
+/// well as `/health` for the probes
+fn requires_auth(app: &AppState, req: &ServiceRequest) -> bool {
+    app.auth.require_auth_for_reads
+        && req.method() != Method::OPTIONS
+        && !req.path().starts_with("/s/")
+        && req.path() != "/health"
+}
+
 // Middleware factory is `Transform` trait
 // `S` - type of the next service
 // `B` - type of response's body
@@ -94,12 +128,15 @@ impl<S> KanimeAuthMiddleware<S> {
         use SessionResult::*;
         if let Some(Ok(val)) = req.headers().get(AUTHORIZATION_HEADER).map(HeaderValue::to_str) {
             if let Some((TOKEN_BASE_TYPE, right)) = val.split_once(' ') {
-                if !validate_nanoid(right, TOKEN_LENGTH) {
+                if !validate_nanoid(right, app.auth.token_length()) {
                     return Ok(Invalid("Bad token formatting", StatusCode::BAD_REQUEST));
                 }
+                if let Some(session) = app.session_cache.get(right) {
+                    return Ok(Valid(session));
+                }
 
-                let raw: Option<String> = app.redis.get_async_connection().await?
-                    .get(format!("{TOKEN_REDIS_KEY_PREFIX}:{right}")).await
+                let raw: Option<String> = app.redis.get().await?
+                    .get(format!("{}:{right}", app.auth.token_prefix())).await
                     .map_err(|e| anyhow!("Get token from redis: {e}"))?;
                 let Some(raw) = raw else {
                     return Ok(Invalid("Token is invalid or has expired", StatusCode::FORBIDDEN));
@@ -111,6 +148,7 @@ impl<S> KanimeAuthMiddleware<S> {
                 if session.expires_on <= now {
                     return Ok(Invalid("Token is invalid or has expired", StatusCode::FORBIDDEN));
                 }
+                app.session_cache.insert(session.clone());
                 return Ok(Valid(session));
             }
         }
@@ -137,11 +175,18 @@ impl<S, B> Service<ServiceRequest> for KanimeAuthMiddleware<S>
             expect("This middleware should always be used with a http server that have an AppState")
             .clone();
         Box::pin(async move {
-            match Self::get_session(app, &req).await {
-                Ok(Anonymous) => svc.call(req).await.map(ServiceResponse::map_into_left_body),
+            match Self::get_session(app.clone(), &req).await {
+                Ok(Anonymous) if requires_auth(&app, &req) => {
+                    Ok(req.into_response(KError::unauthorized().map_into_right_body()))
+                },
+                Ok(Anonymous) => {
+                    req.extensions_mut().insert(AuthContext::anonymous());
+                    svc.call(req).await.map(|res| with_role_rejection(res, &app))
+                },
                 Ok(Valid(session)) => {
+                    req.extensions_mut().insert(AuthContext::from_session(&session));
                     req.extensions_mut().insert(session);
-                    svc.call(req).await.map(ServiceResponse::map_into_left_body)
+                    svc.call(req).await.map(|res| with_role_rejection(res, &app))
                 },
                 Ok(Invalid(msg, code)) => {
                     let res = HttpResponse::build(code).body(msg);
@@ -157,13 +202,63 @@ impl<S, B> Service<ServiceRequest> for KanimeAuthMiddleware<S>
     }
 }
 
+/// Left in the request extensions by a role guard that turned the request down, so that the
+/// fallback can tell a route needing another role apart from one that does not exist
+#[derive(Debug, Clone, Copy)]
+struct RoleRejected;
+
+/// `401` when the request has no session, `403` when its role is not enough, or `None` if no role
+/// guard rejected it (or `auth.hide_guarded_routes` is set) and the usual fallback applies
+pub fn role_rejection_response(req: &HttpRequest, app: &AppState) -> Option<HttpResponse> {
+    if app.auth.hide_guarded_routes {
+        return None;
+    }
+    let exts = req.extensions();
+    exts.get::<RoleRejected>()?;
+    Some(match exts.get::<AuthContext>() {
+        Some(AuthContext { authenticated: true, .. }) => KError::forbidden(),
+        _ => KError::unauthorized()
+    })
+}
+
+/// A resource whose routes were all rejected by their guards answers `405`, it is replaced when a
+/// role guard was among them. Unmatched paths go through `default_endpoint` instead.
+fn with_role_rejection<B>(res: ServiceResponse<B>, app: &AppState) -> ServiceResponse<EitherBody<B>> {
+    if res.status() != StatusCode::METHOD_NOT_ALLOWED {
+        return res.map_into_left_body();
+    }
+    match role_rejection_response(res.request(), app) {
+        Some(rejection) => res.into_response(rejection.map_into_right_body()),
+        None => res.map_into_left_body()
+    }
+}
+
+fn check_role(req: &GuardContext, allowed: impl FnOnce(Role) -> bool) -> bool {
+    let ok = {
+        let exts = req.req_data();
+        matches!(exts.get::<AuthContext>(), Some(AuthContext { role: Some(role), .. }) if allowed(*role))
+    };
+    if !ok {
+        req.req_data_mut().insert(RoleRejected);
+    }
+    ok
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct RequireRoleGuard(pub Role);
 
 impl Guard for RequireRoleGuard {
     fn check(&self, req: &GuardContext) -> bool {
-        let exts = req.req_data();
-        let session: Option<&Session> = exts.get();
-        matches!(session, Some(session) if session.role == self.0)
+        check_role(req, |role| role == self.0)
+    }
+}
+
+/// Like [`RequireRoleGuard`] but also accepts any role with more privileges
+#[derive(Debug, Clone, Copy)]
+pub struct RequireMinRoleGuard(pub Role);
+
+impl Guard for RequireMinRoleGuard {
+    fn check(&self, req: &GuardContext) -> bool {
+        check_role(req, |role| role >= self.0)
     }
 }
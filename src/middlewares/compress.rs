@@ -0,0 +1,161 @@
+use std::future::{Future, Ready, ready};
+use std::io::Write;
+use std::pin::Pin;
+use std::rc::Rc;
+
+use actix_web::{
+    Error, web,
+    body::{EitherBody, MessageBody},
+    web::Bytes,
+    http::header::{self, HeaderMap, HeaderValue},
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+};
+use log::warn;
+
+use crate::config::CompressionConfig;
+
+const BROTLI_BUFFER_SIZE: usize = 4096;
+const BROTLI_WINDOW_SIZE: u32 = 22;
+// compressing smaller bodies costs more than it saves
+const MIN_COMPRESSED_SIZE: usize = 1024;
+const COMPRESSIBLE_TYPES: [&str; 3] = ["application/json", "application/xml", "text/"];
+
+#[derive(Debug, Clone, Copy)]
+enum Encoding {
+    Brotli(u32),
+    Gzip(u32),
+}
+
+impl Encoding {
+    fn header_value(self) -> HeaderValue {
+        HeaderValue::from_static(match self {
+            Encoding::Brotli(_) => "br",
+            Encoding::Gzip(_) => "gzip",
+        })
+    }
+
+    fn encode(self, body: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Encoding::Brotli(quality) => {
+                let mut writer = brotli::CompressorWriter::new(Vec::new(), BROTLI_BUFFER_SIZE,
+                    quality, BROTLI_WINDOW_SIZE);
+                writer.write_all(body)?;
+                writer.flush()?;
+                Ok(writer.into_inner())
+            },
+            Encoding::Gzip(level) => {
+                let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::new(level));
+                encoder.write_all(body)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+/// Whether the `Accept-Encoding` header lists `coding` without a zero quality
+fn accepts(headers: &HeaderMap, coding: &str) -> bool {
+    headers.get_all(header::ACCEPT_ENCODING)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|item| {
+            let mut parts = item.split(';').map(str::trim);
+            parts.next() == Some(coding) && !parts.any(|p| matches!(p.strip_prefix("q=")
+                .and_then(|q| q.parse::<f32>().ok()), Some(q) if q <= 0.))
+        })
+}
+
+fn is_compressible(headers: &HeaderMap) -> bool {
+    if headers.contains_key(header::CONTENT_ENCODING) {
+        return false;
+    }
+    match headers.get(header::CONTENT_TYPE).and_then(|value| value.to_str().ok()) {
+        Some(content_type) => COMPRESSIBLE_TYPES.iter().any(|t| content_type.starts_with(t)),
+        None => false
+    }
+}
+
+/// Compresses buffered text responses with the configured brotli quality or gzip level. It must
+/// be wrapped by [`actix_web::middleware::Compress`] which skips what is already encoded here and
+/// still takes care of streamed bodies and of the algorithms left unset.
+pub struct TunedCompress(pub CompressionConfig);
+
+impl<S, B> Transform<S, ServiceRequest> for TunedCompress
+    where
+        S: Service<ServiceRequest, Response=ServiceResponse<B>, Error=Error> + 'static,
+        S::Future: 'static,
+        B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B, Bytes>>;
+    type Error = Error;
+    type Transform = TunedCompressMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(TunedCompressMiddleware { service: Rc::new(service), config: self.0 }))
+    }
+}
+
+pub struct TunedCompressMiddleware<S> {
+    service: Rc<S>,
+    config: CompressionConfig,
+}
+
+impl<S> TunedCompressMiddleware<S> {
+    fn pick_encoding(&self, headers: &HeaderMap) -> Option<Encoding> {
+        match (self.config.brotli_quality, self.config.gzip_level) {
+            (Some(quality), _) if accepts(headers, "br") => Some(Encoding::Brotli(quality)),
+            (_, Some(level)) if accepts(headers, "gzip") => Some(Encoding::Gzip(level)),
+            _ => None
+        }
+    }
+}
+
+impl<S, B> Service<ServiceRequest> for TunedCompressMiddleware<S>
+    where
+        S: Service<ServiceRequest, Response=ServiceResponse<B>, Error=Error> + 'static,
+        S::Future: 'static,
+        B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B, Bytes>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output=Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let encoding = self.pick_encoding(req.headers());
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let res = fut.await?;
+            let Some(encoding) = encoding else {
+                return Ok(res.map_into_left_body());
+            };
+            if !is_compressible(res.headers()) {
+                return Ok(res.map_into_left_body());
+            }
+
+            let (req, res) = res.into_parts();
+            let (res, body) = res.into_parts();
+            let body = match body.try_into_bytes() {
+                Ok(body) if body.len() >= MIN_COMPRESSED_SIZE => body,
+                Ok(body) => return Ok(ServiceResponse::new(req, res.set_body(body).map_into_right_body())),
+                // streamed bodies are left to the default encoder
+                Err(body) => return Ok(ServiceResponse::new(req, res.set_body(body).map_into_left_body())),
+            };
+            let compressed = match web::block(move || encoding.encode(&body).map_err(|e| (e, body))).await {
+                Ok(Ok(compressed)) => compressed,
+                Ok(Err((e, body))) => {
+                    warn!("Could not compress response: {e:?}");
+                    return Ok(ServiceResponse::new(req, res.set_body(body).map_into_right_body()));
+                },
+                Err(e) => return Err(e.into())
+            };
+
+            let mut res = res.set_body(Bytes::from(compressed));
+            res.headers_mut().insert(header::CONTENT_ENCODING, encoding.header_value());
+            res.headers_mut().append(header::VARY, HeaderValue::from_static("accept-encoding"));
+            Ok(ServiceResponse::new(req, res.map_into_right_body()))
+        })
+    }
+}
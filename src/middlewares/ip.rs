@@ -1,9 +1,24 @@
 use std::future::{Ready, ready};
 use std::net::{IpAddr, SocketAddr};
 use actix_web::{dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform}, Error};
+use actix_web::{HttpMessage, HttpRequest};
 use anyhow::{anyhow, Result};
 
 const CLOUDFLARE_IP_HEADER: &str = "CF-Connecting-IP";
+const FORWARDED_PROTO_HEADER: &str = "X-Forwarded-Proto";
+
+/// Scheme the client used to reach the proxy, only set for requests that came through it
+#[derive(Debug, Clone, Copy)]
+struct ForwardedScheme(&'static str);
+
+/// Scheme the client used, the connection one unless the request came through the proxy
+pub fn request_scheme(req: &HttpRequest) -> &'static str {
+    match req.extensions().get::<ForwardedScheme>() {
+        Some(ForwardedScheme(scheme)) => scheme,
+        None if req.app_config().secure() => "https",
+        None => "http"
+    }
+}
 
 pub struct CloudflareClientIp;
 
@@ -39,6 +54,14 @@ impl<S> CloudflareClientIpMiddleware<S> {
         let local = req.peer_addr().ok_or_else(|| anyhow!("No peer addr"))?.port();
         Ok(SocketAddr::new(peer_addr, local))
     }
+
+    fn forwarded_scheme(req: &ServiceRequest) -> Option<ForwardedScheme> {
+        match req.headers().get(FORWARDED_PROTO_HEADER)?.to_str().ok()? {
+            "https" => Some(ForwardedScheme("https")),
+            "http" => Some(ForwardedScheme("http")),
+            _ => None
+        }
+    }
 }
 
 impl<S, B> Service<ServiceRequest> for CloudflareClientIpMiddleware<S>
@@ -54,8 +77,12 @@ impl<S, B> Service<ServiceRequest> for CloudflareClientIpMiddleware<S>
     forward_ready!(service);
 
     fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        // the forwarded scheme is as trustworthy as the forwarded IP, both come from the proxy
         if let Ok(ip) = Self::header_value_to_ip(&req) {
             req.head_mut().peer_addr = Some(ip);
+            if let Some(scheme) = Self::forwarded_scheme(&req) {
+                req.extensions_mut().insert(scheme);
+            }
         }
 
         self.service.call(req)
@@ -1,2 +1,5 @@
 pub mod auth;
+pub mod compress;
 pub mod ip;
+pub mod request_id;
+pub mod timeout;
@@ -0,0 +1,90 @@
+use std::future::{Future, Ready, ready};
+use std::pin::Pin;
+
+use actix_web::{
+    Error, HttpMessage,
+    http::header::{HeaderName, HeaderValue},
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+};
+
+use super::auth::NANOID_ALPHABET;
+
+const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+const REQUEST_ID_LENGTH: usize = 21;
+// longer ids sent by clients are replaced rather than written to the logs
+const MAX_REQUEST_ID_LENGTH: usize = 64;
+
+/// Identifier of the request, taken from `X-Request-Id` or generated when absent
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+pub fn pick_request_id(req: &ServiceRequest) -> String {
+    match req.extensions().get::<RequestId>() {
+        Some(RequestId(id)) => id.clone(),
+        None => "-".to_string()
+    }
+}
+
+fn generate_request_id() -> String {
+    let alphabet: String = NANOID_ALPHABET.iter().collect();
+    random_string::generate(REQUEST_ID_LENGTH, &alphabet)
+}
+
+fn incoming_request_id(req: &ServiceRequest) -> Option<String> {
+    let id = req.headers().get(REQUEST_ID_HEADER)?.to_str().ok()?;
+    let valid = !id.is_empty() && id.len() <= MAX_REQUEST_ID_LENGTH
+        && id.bytes().all(|b| b.is_ascii_graphic());
+    valid.then(|| id.to_string())
+}
+
+/// Stores the [`RequestId`] in the request extensions and echoes it in the response headers. It
+/// must wrap the `Logger` for the id to be known when the access log is written.
+pub struct RequestIdHeader;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestIdHeader
+    where
+        S: Service<ServiceRequest, Response=ServiceResponse<B>, Error=Error>,
+        S::Future: 'static,
+        B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestIdMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestIdMiddleware { service }))
+    }
+}
+
+pub struct RequestIdMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestIdMiddleware<S>
+    where
+        S: Service<ServiceRequest, Response=ServiceResponse<B>, Error=Error>,
+        S::Future: 'static,
+        B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output=Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let id = incoming_request_id(&req).unwrap_or_else(generate_request_id);
+        let value = HeaderValue::from_str(&id);
+        req.extensions_mut().insert(RequestId(id));
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let mut res = fut.await?;
+            if let Ok(value) = value {
+                res.headers_mut().insert(REQUEST_ID_HEADER, value);
+            }
+            Ok(res)
+        })
+    }
+}
@@ -0,0 +1,87 @@
+use std::future::{Future, Ready, ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::time::Duration;
+
+use actix_web::{
+    Error,
+    body::EitherBody,
+    rt::time::timeout,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+};
+use log::warn;
+
+use crate::types::KError;
+
+/// Answers with a `504` when the inner services take longer than the given duration to respond,
+/// except on the exempted paths whose work must not be dropped halfway
+pub struct RequestTimeout {
+    duration: Duration,
+    exempt: &'static [&'static str],
+}
+
+impl RequestTimeout {
+    pub fn new(duration: Duration) -> Self {
+        Self { duration, exempt: &[] }
+    }
+
+    pub fn exempting(self, exempt: &'static [&'static str]) -> Self {
+        Self { exempt, ..self }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequestTimeout
+    where
+        S: Service<ServiceRequest, Response=ServiceResponse<B>, Error=Error> + 'static,
+        S::Future: 'static,
+        B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RequestTimeoutMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestTimeoutMiddleware { service: Rc::new(service), duration: self.duration, exempt: self.exempt }))
+    }
+}
+
+pub struct RequestTimeoutMiddleware<S> {
+    service: Rc<S>,
+    duration: Duration,
+    exempt: &'static [&'static str],
+}
+
+impl<S, B> Service<ServiceRequest> for RequestTimeoutMiddleware<S>
+    where
+        S: Service<ServiceRequest, Response=ServiceResponse<B>, Error=Error> + 'static,
+        S::Future: 'static,
+        B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output=Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        if self.exempt.contains(&req.path()) {
+            let fut = self.service.call(req);
+            return Box::pin(async move { fut.await.map(ServiceResponse::map_into_left_body) });
+        }
+        // the request is moved into the inner service, keep what is needed to answer without it
+        let http_req = req.request().clone();
+        let fut = self.service.call(req);
+        let duration = self.duration;
+        Box::pin(async move {
+            match timeout(duration, fut).await {
+                Ok(res) => res.map(ServiceResponse::map_into_left_body),
+                Err(_) => {
+                    warn!(target: "http", "{} {} timed out after {duration:?}", http_req.method(), http_req.path());
+                    Ok(ServiceResponse::new(http_req, KError::gateway_timeout().map_into_right_body()))
+                }
+            }
+        })
+    }
+}
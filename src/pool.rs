@@ -0,0 +1,85 @@
+use std::sync::Arc;
+use futures::lock::Mutex;
+use redis::{Cmd, Pipeline, RedisError, RedisFuture, RedisResult, Value};
+use redis::aio::{ConnectionLike, MultiplexedConnection};
+use log::warn;
+
+/// A multiplexed Redis connection shared by every request of a worker. It is opened on first use
+/// and dropped as soon as a command fails at the connection level, the next caller reconnects.
+#[derive(Clone)]
+pub struct RedisPool {
+    client: redis::Client,
+    current: Arc<Mutex<Option<(u64, MultiplexedConnection)>>>,
+}
+
+impl RedisPool {
+    pub fn new(client: redis::Client) -> Self {
+        Self { client, current: Arc::new(Mutex::new(None)) }
+    }
+
+    pub async fn get(&self) -> RedisResult<PooledConnection> {
+        let mut current = self.current.lock().await;
+        let (generation, inner) = match &*current {
+            Some((generation, con)) => (*generation, con.clone()),
+            None => {
+                let con = self.client.get_multiplexed_tokio_connection().await?;
+                // tells a broken connection apart from the one that already replaced it
+                let generation = next_generation();
+                *current = Some((generation, con.clone()));
+                (generation, con)
+            }
+        };
+        Ok(PooledConnection { inner, generation, pool: self.clone() })
+    }
+
+    async fn discard(&self, generation: u64) {
+        let mut current = self.current.lock().await;
+        if matches!(&*current, Some((g, _)) if *g == generation) {
+            warn!(target: "redis", "Connection lost, reconnecting on next use");
+            *current = None;
+        }
+    }
+}
+
+fn next_generation() -> u64 {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
+fn is_broken(e: &RedisError) -> bool {
+    e.is_io_error() || e.is_connection_dropped() || e.is_connection_refusal() || e.is_timeout()
+}
+
+pub struct PooledConnection {
+    inner: MultiplexedConnection,
+    generation: u64,
+    pool: RedisPool,
+}
+
+impl ConnectionLike for PooledConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        Box::pin(async move {
+            let res = self.inner.req_packed_command(cmd).await;
+            if matches!(&res, Err(e) if is_broken(e)) {
+                self.pool.discard(self.generation).await;
+            }
+            res
+        })
+    }
+
+    fn req_packed_commands<'a>(&'a mut self, cmd: &'a Pipeline, offset: usize, count: usize)
+        -> RedisFuture<'a, Vec<Value>> {
+        Box::pin(async move {
+            let res = self.inner.req_packed_commands(cmd, offset, count).await;
+            if matches!(&res, Err(e) if is_broken(e)) {
+                self.pool.discard(self.generation).await;
+            }
+            res
+        })
+    }
+
+    fn get_db(&self) -> i64 {
+        self.inner.get_db()
+    }
+}
@@ -1,29 +1,104 @@
-use actix_web::{guard, get, web::{self, Data, Json, Path, Form}, Responder, HttpResponse};
-use mongodb::{bson::{doc, oid::ObjectId}, results::InsertOneResult};
+use actix_web::{guard, web::{self, Data, Json, Path, Form, Query, ReqData}, Responder, HttpResponse, HttpRequest};
+use actix_web::error::{InternalError, PayloadError};
+use actix_web::http::{StatusCode, header::{self, CacheControl, CacheDirective}};
+use mongodb::{bson::{self, doc, oid::ObjectId}, results::InsertOneResult};
 use serde::{Deserialize, Serialize};
 use anyhow::{Context, Result, anyhow, bail};
 use log::{error, warn, info};
-use meilisearch_sdk::errors::{Error, ErrorCode, MeilisearchError};
-use mongodb::{Client, options::FindOptions};
-use actix_easy_multipart::MultipartForm;
+use meilisearch_sdk::errors::{Error, ErrorCode, ErrorType, MeilisearchError};
+use meilisearch_sdk::search::{SearchQuery as MeiliSearchQuery, SearchResults};
+use actix_web::rt::time::timeout;
+use serde::de::DeserializeOwned;
+use meilisearch_sdk::documents::DocumentsQuery;
+use meilisearch_sdk::{indexes::Index, search::Selectors, settings::Settings, tasks::Task, task_info::TaskInfo};
+use std::time::{Duration, SystemTime};
+use mongodb::{Client, IndexModel};
+use mongodb::options::{Collation, CollationStrength, FindOptions, FindOneOptions, IndexOptions, ReplaceOptions};
+use actix_easy_multipart::{MultipartForm, MultipartFormConfig};
+use actix_easy_multipart::actix_multipart::MultipartError;
 use actix_easy_multipart::tempfile::Tempfile;
+use actix_easy_multipart::text::Text;
 use std::fs::File;
+use std::collections::BTreeSet;
+use unicode_normalization::UnicodeNormalization;
+use futures::stream::{Stream, StreamExt, TryStreamExt};
+use redis::AsyncCommands;
+use actix_web::web::Bytes;
 
 use crate::gen::anime::*;
+use crate::config::{AuthConfig, CatalogConfig, SearchConfig};
 use crate::types::*;
-use crate::middlewares::auth::{Role, RequireRoleGuard};
-use super::seo;
+use crate::middlewares::auth::{Role, RequireRoleGuard, RequireMinRoleGuard, Session};
+use crate::lock::RedisLock;
+use crate::pool::RedisPool;
+use crate::jobs::{spawn_job, JobHandle, JobProgress};
+use super::{cache_control, options, seo, CacheKind};
+use super::images::{serve_fallback_poster, serve_image};
+use super::jobs::{fetch_job, job_accepted};
 
 const CACHE_KEY_ALPHABET: &str = "ABCDEFGHIJKMNOPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz0123456789";
 
 const DB_NAME: &str = "Kanime3";
+const OBJECT_ID_LENGTH: usize = 24;
 const COLL_NAME: &str = "animes";
 const ANIMES_INDEX: &str = "animes";
 const ANIMES_INDEX_BATCH_SIZE: usize = 32;
+// log progress every 32 batches
+const ANIMES_INDEX_PROGRESS_STEP: usize = 32 * ANIMES_INDEX_BATCH_SIZE;
+const ANIMES_VERIFY_BATCH_SIZE: usize = 1000;
+pub const ANIMES_INDEX_LOCK: &str = "animes-index";
+pub const ANIMES_INDEX_LOCK_TTL_MS: usize = 10 * 60 * 1000;
+const ANIMES_REINDEX_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+// greatest `updatedOn` already sent to the index
+const ANIMES_INDEX_WATERMARK_KEY: &str = "animes-index:watermark";
 const ANIMES_SEARCH_QUERY_MIN_LEN: usize = 2;
 const ANIMES_SEARCH_QUERY_MAX_LEN: usize = 128;
-const ANIMES_SEARCH_DEFAULT_LIMIT: u32 = 10;
-const ANIMES_SEARCH_SOFT_LIMIT: u32 = 100;
+const ANIMES_SUGGEST_QUERY_MIN_LEN: usize = 1;
+const ANIMES_SUGGEST_LIMIT: usize = 8;
+const ANIMES_LIST_DEFAULT_LIMIT: u32 = 50;
+const ANIMES_LIST_SOFT_LIMIT: u32 = 500;
+const ANIMES_RECENT_DEFAULT_LIMIT: u32 = 20;
+const ANIMES_RECENT_SOFT_LIMIT: u32 = 100;
+// Meilisearch stops returning hits past `pagination.maxTotalHits` (1000 by default)
+const ANIMES_SEARCH_MAX_OFFSET: u32 = 1000;
+const NEXT_OFFSET_HEADER: &str = "X-Next-Offset";
+const SEARCH_DEGRADED_HEADER: &str = "X-Search-Degraded";
+const ANIMES_SEARCH_FALLBACK_LIMIT: u32 = 10;
+const ANIME_SLUG_INSERT_ATTEMPTS: u32 = 3;
+const MONGODB_DUPLICATE_KEY: i32 = 11000;
+const UNIQUE_TITLES_INDEX: &str = "normalizedTitles_unique_titled";
+// sparse, it indexed the empty titles of every untitled anime as the same key
+const LEGACY_UNIQUE_TITLES_INDEX: &str = "normalizedTitles_unique";
+const HAS_MORE_HEADER: &str = "X-Has-More";
+const ESTIMATED_TOTAL_HITS_HEADER: &str = "X-Estimated-Total-Hits";
+// tells an empty page past the last result apart from a query without results
+const OFFSET_OUT_OF_RANGE_HEADER: &str = "X-Offset-Out-Of-Range";
+const ANIMES_SORTABLE_ATTRIBUTES: [&str; 2] = ["releaseYear", "titles"];
+const ANIMES_SEARCH_SORTS: [&str; 4] = ["releaseYear:asc", "releaseYear:desc", "titles:asc", "titles:desc"];
+const ANIMES_SEARCH_STREAM_PAGE_SIZE: usize = 100;
+const ANIMES_EXPORT_CHUNK_SIZE: usize = 100; // animes per streamed chunk of the export
+pub const ANIMES_IMPORT_PATH: &str = "/s/anime/import";
+const ANIMES_IMPORT_BATCH_SIZE: usize = 100;
+const ANIMES_IMPORT_MAX_LINE_BYTES: usize = 1024 * 1024; // far above any anime document
+const ANIMES_IMPORT_MAX_REPORTED_ERRORS: usize = 50;
+const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+const ANIMES_SEARCH_MAX_SORTS: usize = 2;
+const PREVIEW_DEFAULT_WIDTH: u32 = 32;
+const PREVIEW_DEFAULT_HEIGHT: u32 = 48;
+const PREVIEW_MAX_WIDTH: u32 = 64;
+const PREVIEW_MAX_HEIGHT: u32 = 96;
+const BULK_DELETE_MAX_IDS: usize = 100;
+const NOTE_MAX_LEN: usize = 1024;
+// presenters are rendered on the blocking pool, keep some room for uploads
+const PRESENTER_REGENERATION_CONCURRENCY: usize = 2;
+const PRESENTER_REGENERATION_JOB: &str = "presenter-regeneration";
+const REINDEX_JOB: &str = "reindex";
+const SYNC_JOB: &str = "sync";
+const BACKUP_FOLDER: &str = "deleted";
+const BACKUP_EXTENSION: &str = ".json";
+const MULTIPART_MEMORY_LIMIT: usize = 2 * 1024 * 1024; // 2 MiB
+const ANIMES_SEARCHABLE_ATTRIBUTES: [&str; 3] = ["titles", "synonyms", "author"];
+const ANIMES_FILTERABLE_ATTRIBUTES: [&str; 1] = ["kinds"];
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -32,24 +107,145 @@ pub struct SearchQuery {
     offset: Option<u32>,
     limit: Option<u32>,
     #[serde(default)]
-    display_matches: bool
+    display_matches: bool,
+    #[serde(default)]
+    highlight: bool,
+    #[serde(default, deserialize_with = "string_or_seq")]
+    sort: Vec<String>,
+    /// Only matches animes with at least one season of this kind
+    kind: Option<String>,
+    #[serde(skip)]
+    filter: Option<String>,
+}
+
+/// Trims and NFC-normalizes a user query so its length can be checked in characters
+fn normalize_query(query: &str) -> String {
+    query.trim().nfc().collect()
 }
 
 impl SearchQuery {
+    pub fn normalize(&mut self) {
+        self.query = normalize_query(&self.query);
+    }
+
     pub fn validate(&self) -> bool {
-        self.query.len() >= ANIMES_SEARCH_QUERY_MIN_LEN &&
-            self.query.len() <= ANIMES_SEARCH_QUERY_MAX_LEN
+        let len = self.query.chars().count();
+        (ANIMES_SEARCH_QUERY_MIN_LEN..=ANIMES_SEARCH_QUERY_MAX_LEN).contains(&len)
+    }
+
+    /// Parses the requested season kind into a Meilisearch filter, `false` if the kind is unknown
+    pub fn parse_kind(&mut self) -> bool {
+        let Some(kind) = &self.kind else {
+            return true;
+        };
+        match SeasonKind::parse(kind) {
+            Some(kind) => {
+                self.filter = Some(format!("kinds = {}", kind.as_str()));
+                true
+            },
+            None => false
+        }
+    }
+
+    pub fn validate_sort(&self) -> bool {
+        self.sort.len() <= ANIMES_SEARCH_MAX_SORTS &&
+            self.sort.iter().all(|s| ANIMES_SEARCH_SORTS.contains(&s.as_str()))
+    }
+}
+
+/// Accepts either a list of strings (JSON) or a single comma separated string (forms)
+fn string_or_seq<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrSeq {
+        String(String),
+        Seq(Vec<String>),
+    }
+    Ok(match StringOrSeq::deserialize(deserializer)? {
+        StringOrSeq::String(s) => s.split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+            .collect(),
+        StringOrSeq::Seq(seq) => seq,
+    })
+}
+
+/// Why a path segment is not an ObjectId, displayed as the error description
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidObjectId {
+    WrongLength(usize),
+    InvalidCharacters,
+}
+
+impl std::fmt::Display for InvalidObjectId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InvalidObjectId::WrongLength(len) =>
+                write!(f, "The provided ID must be {OBJECT_ID_LENGTH} characters long, found {len}"),
+            InvalidObjectId::InvalidCharacters =>
+                write!(f, "The provided ID must only contain hexadecimal characters"),
+        }
+    }
+}
+
+pub fn to_oid(id: &str) -> Result<ObjectId, InvalidObjectId> {
+    let len = id.chars().count();
+    if len != OBJECT_ID_LENGTH {
+        return Err(InvalidObjectId::WrongLength(len));
     }
+    ObjectId::parse_str(id).map_err(|_| InvalidObjectId::InvalidCharacters)
 }
 
-fn to_oid(id: &str) -> Option<ObjectId> {
-    if id.len() != 24 { // ObjectId length
+/// Returns the attribute list extended with the missing required ones, or `None` if nothing
+/// is missing. A wildcard is replaced by the required attributes.
+fn with_required_attributes(mut current: Vec<String>, required: &[&str]) -> Option<Vec<String>> {
+    if current.iter().any(|a| a == "*") {
+        current.clear();
+    }
+    let missing: Vec<String> = required.iter()
+        .filter(|a| !current.iter().any(|c| c == *a))
+        .map(|a| a.to_string())
+        .collect();
+    if missing.is_empty() {
+        return None;
+    }
+    current.extend(missing);
+    Some(current)
+}
+
+/// The attribute list the index should have, or `None` if it already does. A configured list is
+/// applied as is, with the required attributes it lacks appended, otherwise the required ones are
+/// only added to the current list. Only the searchable attributes are ordered, by importance.
+fn desired_attributes(kind: &str, current: Vec<String>, configured: Option<&Vec<String>>,
+                      required: &[&str], ordered: bool) -> Option<Vec<String>> {
+    let Some(configured) = configured else {
+        return with_required_attributes(current, required);
+    };
+    let mut desired = configured.clone();
+    desired.extend(required.iter()
+        .filter(|a| !configured.iter().any(|c| c == *a))
+        .map(|a| a.to_string()));
+    let unchanged = if ordered {
+        current == desired
+    } else {
+        let mut current = current.clone();
+        let mut sorted = desired.clone();
+        current.sort_unstable();
+        sorted.sort_unstable();
+        current == sorted
+    };
+    if unchanged {
         return None;
     }
-    ObjectId::parse_str(id).ok()
+    info!(target: "meilisearch", "The {kind} attributes of index `{ANIMES_INDEX}` differ from the configuration, \
+        replacing {current:?} with {desired:?}");
+    Some(desired)
 }
 
-pub async fn sync_meilisearch(mongodb: &Client, meilisearch: &meilisearch_sdk::Client) -> Result<()> {
+/// Makes sure the index exists with the configured attributes and the ones we rely on, also
+/// returns whether every document has to be pushed again because filterable attributes changed
+async fn prepare_index(meilisearch: &meilisearch_sdk::Client, search: &SearchConfig) -> Result<(Index, bool)> {
     let index = match meilisearch.get_index(ANIMES_INDEX).await {
         Ok(index) => index,
         Err(Error::Meilisearch(MeilisearchError { error_code: ErrorCode::IndexNotFound, .. })) => {
@@ -59,84 +255,591 @@ pub async fn sync_meilisearch(mongodb: &Client, meilisearch: &meilisearch_sdk::C
                 .try_make_index(meilisearch)
                 .map_err(|t| anyhow!("Failed to create index `{ANIMES_INDEX}`: {t:?}"))?;
             info!(target: "meilisearch","Successfully created index `{ANIMES_INDEX}`");
-
-            index.set_searchable_attributes(&["titles", "author"]).await?
-                .wait_for_completion(meilisearch, None, None).await?;
-            info!(target: "meilisearch","Setup completed for index `{ANIMES_INDEX}`");
             index
         },
         Err(e) => bail!("{e}"),
     };
 
+    // Without a configured list, settings tuned through the admin endpoint are kept
+    let searchable = index.get_searchable_attributes().await?;
+    if let Some(searchable) = desired_attributes("searchable", searchable, search.searchable_attributes.as_ref(),
+        &ANIMES_SEARCHABLE_ATTRIBUTES, true) {
+        index.set_searchable_attributes(&searchable).await?
+            .wait_for_completion(meilisearch, None, None).await?;
+        info!(target: "meilisearch","Setup completed for index `{ANIMES_INDEX}`");
+    }
+    let sortable = index.get_sortable_attributes().await?;
+    if let Some(sortable) = desired_attributes("sortable", sortable, search.sortable_attributes.as_ref(),
+        &ANIMES_SORTABLE_ATTRIBUTES, false) {
+        index.set_sortable_attributes(&sortable).await?
+            .wait_for_completion(meilisearch, None, None).await?;
+        info!(target: "meilisearch","Updated sortable attributes for index `{ANIMES_INDEX}`");
+    }
+    let filterable = index.get_filterable_attributes().await?;
+    let repush = match desired_attributes("filterable", filterable, search.filterable_attributes.as_ref(),
+        &ANIMES_FILTERABLE_ATTRIBUTES, false) {
+        Some(filterable) => {
+            index.set_filterable_attributes(&filterable).await?
+                .wait_for_completion(meilisearch, None, None).await?;
+            info!(target: "meilisearch","Updated filterable attributes for index `{ANIMES_INDEX}`");
+            true
+        },
+        None => false
+    };
+    Ok((index, repush))
+}
+
+async fn read_watermark(redis: &RedisPool) -> Result<Option<u64>> {
+    let mut con = redis.get().await?;
+    Ok(con.get(ANIMES_INDEX_WATERMARK_KEY).await?)
+}
+
+async fn save_watermark(redis: &RedisPool, watermark: u64) -> Result<()> {
+    let mut con = redis.get().await?;
+    con.set::<_, _, ()>(ANIMES_INDEX_WATERMARK_KEY, watermark).await?;
+    Ok(())
+}
+
+/// Greatest `updatedOn` of the collection, `0` when it is empty
+async fn latest_update(col: &mongodb::Collection<WithOID<AnimeSeries>>) -> Result<u64> {
+    let latest = col.find_one(None, FindOneOptions::builder()
+            .sort(doc! { "updatedOn": -1 })
+            .build())
+        .await?;
+    Ok(latest.map_or(0, |anime| anime.as_ref().updated_on))
+}
+
+/// Animes [`AnimeSeriesSearchEntry::normalized`] accepts, the ones the index should hold
+fn indexable_filter() -> bson::Document {
+    doc! {
+        "titles": { "$elemMatch": { "value": { "$regex": "\\S" } } },
+        "manga.author": { "$regex": "\\S" }
+    }
+}
+
+/// What was sent to the index by a push
+#[derive(Debug, Default)]
+pub struct PushSummary {
+    pub sent: usize,
+    /// Documents left out because they could not be read or had nothing to search on
+    pub skipped: usize,
+    /// Last enqueued task, if any
+    pub last_task: Option<TaskInfo>,
+    skipped_ids: Vec<String>,
+}
+
+/// Keeps the index in sync with the collection. The document counts are compared to detect
+/// drift such as missed deletions, in which case the whole collection is pushed again, otherwise
+/// only the animes updated since the last sync are sent. Running it on an index already in sync
+/// does nothing. Malformed animes are logged and left out of the index.
+pub async fn sync_meilisearch(mongodb: &Client, meilisearch: &meilisearch_sdk::Client, redis: &RedisPool,
+                              search: &SearchConfig) -> Result<PushSummary> {
+    let (index, repush) = prepare_index(meilisearch, search).await?;
+
     let col: mongodb::Collection<WithOID<AnimeSeries>> = mongodb.database(DB_NAME).collection(COLL_NAME);
-    let anime_count = col.count_documents(None, None).await? as usize;
+    // the malformed animes are never in the index, they must not be seen as drift
+    let anime_count = col.count_documents(indexable_filter(), None).await? as usize;
+    // taken before pushing so that animes updated meanwhile are sent again next time
+    let latest = latest_update(&col).await?;
 
     let index_stats = index.get_stats().await?;
-    if index_stats.number_of_documents == anime_count {
-        return Ok(());
+    // documents indexed before the attribute existed do not have it
+    let watermark = if repush { None } else { read_watermark(redis).await? };
+    let pushed = match watermark {
+        Some(watermark) if index_stats.number_of_documents == anime_count => {
+            if latest <= watermark {
+                info!(target: "meilisearch", "Index `{ANIMES_INDEX}` is already in sync ({anime_count} documents)");
+                return Ok(PushSummary::default());
+            }
+            sync_incremental(&col, &index, watermark).await?
+        },
+        _ => {
+            if index_stats.number_of_documents != anime_count {
+                info!(target: "meilisearch",
+                    "Sync required for index `{ANIMES_INDEX}`: entry count mismatch, expected {anime_count} but found {}",
+                    index_stats.number_of_documents);
+            } else if repush {
+                info!(target: "meilisearch", "Filterable attributes changed for index `{ANIMES_INDEX}`, pushing everything");
+            } else {
+                info!(target: "meilisearch", "No sync watermark for index `{ANIMES_INDEX}`, pushing everything");
+            }
+            push_documents(&col, &index, None).await?
+        }
+    };
+    save_watermark(redis, latest).await?;
+    if pushed.skipped > 0 {
+        warn!(target: "meilisearch", "Skipped {} malformed animes while syncing `{ANIMES_INDEX}`", pushed.skipped);
+    }
+    info!(target: "meilisearch", "Sync completed successfully!");
+
+    Ok(pushed)
+}
+
+/// Pushes the animes updated after `watermark`, the ones that became malformed are removed from
+/// the index instead of being left with their previous values.
+async fn sync_incremental(col: &mongodb::Collection<WithOID<AnimeSeries>>, index: &Index, watermark: u64)
+    -> Result<PushSummary> {
+    let mut pushed = push_documents(col, index, Some(doc! { "updatedOn": { "$gt": watermark as i64 } })).await?;
+    if !pushed.skipped_ids.is_empty() {
+        pushed.last_task = Some(index.delete_documents(&pushed.skipped_ids).await?);
     }
-    info!(target: "meilisearch",
-        "Sync required for index `{ANIMES_INDEX}`: entry count mismatch, expected {anime_count} but found {}",
-        index_stats.number_of_documents);
+    info!(target: "meilisearch", "Sent {} animes updated since the last sync to `{}`", pushed.sent, index.uid);
+    Ok(pushed)
+}
 
+/// Streams the matching animes, the whole collection without a filter, into the index in batches.
+/// Animes that cannot be read or normalized are logged and skipped instead of failing the push.
+async fn push_documents(col: &mongodb::Collection<WithOID<AnimeSeries>>, index: &Index,
+    filter: Option<bson::Document>) -> Result<PushSummary> {
     let mut cur = col
-        .find(doc! {}, FindOptions::builder()
+        .find(filter, FindOptions::builder()
             .batch_size(ANIMES_INDEX_BATCH_SIZE as u32).build())
         .await?;
     let mut queue: Vec<AnimeSeriesSearchEntry>
         = Vec::with_capacity(ANIMES_INDEX_BATCH_SIZE);
+    let mut summary = PushSummary::default();
     while cur.advance().await? {
-        let current: WithOID<AnimeSeries> = cur.deserialize_current()?;
-        queue.push(current.into());
+        let entry = cur.deserialize_current()
+            .map_err(|e| e.to_string())
+            .and_then(|current: WithOID<AnimeSeries>| AnimeSeriesSearchEntry::from(current).normalized());
+        match entry {
+            Ok(entry) => queue.push(entry),
+            Err(reason) => {
+                summary.skipped += 1;
+                match cur.current().get_object_id("_id") {
+                    Ok(id) => {
+                        warn!(target: "meilisearch", "Skipping anime `{}`: {reason}", id.to_hex());
+                        summary.skipped_ids.push(id.to_hex());
+                    },
+                    Err(_) => warn!(target: "meilisearch", "Skipping anime without a valid ID: {reason}")
+                }
+                continue;
+            }
+        }
         if queue.len() == ANIMES_INDEX_BATCH_SIZE {
-            index.add_or_replace(&queue, Some(ANIME_PRIMARY_KEY)).await?;
+            summary.last_task = Some(index.add_or_replace(&queue, Some(ANIME_PRIMARY_KEY)).await?);
+            summary.sent += queue.len();
             queue.clear();
+            if summary.sent % ANIMES_INDEX_PROGRESS_STEP == 0 {
+                info!(target: "meilisearch", "Sent {} documents to `{}`...", summary.sent, index.uid);
+            }
         }
     }
     if !queue.is_empty() {
-        index.add_or_replace(&queue, Some(ANIME_PRIMARY_KEY)).await?;
+        summary.last_task = Some(index.add_or_replace(&queue, Some(ANIME_PRIMARY_KEY)).await?);
+        summary.sent += queue.len();
     }
-    info!(target: "meilisearch", "Sync completed successfully!");
+    Ok(summary)
+}
 
-    Ok(())
+async fn reindex_meilisearch(app: &AppState) -> Result<usize> {
+    let index = app.meilisearch.get_index(ANIMES_INDEX).await?;
+    index.delete_all_documents().await?
+        .wait_for_completion(&app.meilisearch, None, None).await?;
+    info!(target: "meilisearch", "Cleared index `{ANIMES_INDEX}`, reindexing...");
+
+    let col: mongodb::Collection<WithOID<AnimeSeries>> =
+        app.mongodb.database(DB_NAME).collection(COLL_NAME);
+    let latest = latest_update(&col).await?;
+    let PushSummary { sent: count, skipped, last_task, .. } = push_documents(&col, &index, None).await?;
+    if let Some(task) = last_task {
+        // tasks are processed in order, once the last batch is done everything is
+        task.wait_for_completion(&app.meilisearch, None, Some(ANIMES_REINDEX_TIMEOUT)).await?;
+    }
+    save_watermark(&app.redis, latest).await?;
+    if skipped > 0 {
+        warn!(target: "meilisearch", "Skipped {skipped} malformed animes while reindexing `{ANIMES_INDEX}`");
+    }
+    info!(target: "meilisearch", "Reindexed {count} documents in `{ANIMES_INDEX}`");
+    Ok(count)
+}
+
+async fn reindex(app: Data<AppState>) -> HttpResponse {
+    let lock = match RedisLock::try_acquire(&app.redis, ANIMES_INDEX_LOCK, ANIMES_INDEX_LOCK_TTL_MS).await {
+        Ok(Some(lock)) => lock,
+        Ok(None) => return KError::conflict("A reindex is already in progress"),
+        Err(e) => {
+            error!("Could not acquire reindex lock: {e:?}");
+            return KError::internal_error("Could not acquire reindex lock");
+        }
+    };
+    let job_app = app.clone();
+    let job = spawn_job(&app.redis, REINDEX_JOB, move |progress| async move {
+        let res = reindex_meilisearch(&job_app).await;
+        lock.release(&job_app.redis).await;
+        let count = res?;
+        progress.set_total(count as u64).await;
+        progress.advance_by(count as u64).await;
+        Ok(())
+    }).await;
+    match job {
+        Ok(job) => job_accepted(&job),
+        Err(e) => {
+            error!("Could not start reindex: {e:?}");
+            KError::internal_error("Could not start reindex")
+        }
+    }
+}
+
+/// Starts a sync in the background, returns `None` if a sync or reindex is already running
+async fn start_sync(app: &Data<AppState>) -> Result<Option<JobHandle>> {
+    let Some(lock) = RedisLock::try_acquire(&app.redis, ANIMES_INDEX_LOCK, ANIMES_INDEX_LOCK_TTL_MS).await
+        .context("Acquiring sync lock")? else {
+        return Ok(None);
+    };
+    // runs in the background, it can outlast the request timeout
+    let job_app = app.clone();
+    let job = spawn_job(&app.redis, SYNC_JOB, move |progress| async move {
+        let res = async {
+            let PushSummary { sent: count, last_task, .. } =
+                sync_meilisearch(&job_app.mongodb, &job_app.meilisearch, &job_app.redis, &job_app.search).await?;
+            if let Some(task) = last_task {
+                // wait for the last batch so that the next sync sees the index in sync
+                task.wait_for_completion(&job_app.meilisearch, None, Some(ANIMES_REINDEX_TIMEOUT)).await?;
+            }
+            Ok::<_, anyhow::Error>(count)
+        }.await;
+        lock.release(&job_app.redis).await;
+        let count = res? as u64;
+        progress.set_total(count).await;
+        progress.advance_by(count).await;
+        Ok(())
+    }).await?;
+    Ok(Some(job))
+}
+
+async fn sync(app: Data<AppState>) -> HttpResponse {
+    info!(target: "meilisearch", "Manual sync of `{ANIMES_INDEX}` requested");
+    match start_sync(&app).await {
+        Ok(Some(job)) => job_accepted(&job),
+        Ok(None) => KError::conflict("A sync or reindex is already in progress"),
+        Err(e) => {
+            error!("Could not start sync: {e:?}");
+            KError::internal_error("Could not start sync")
+        }
+    }
+}
+
+enum SearchFailure {
+    TimedOut,
+    /// Happens on a fresh Meilisearch until the first sync created the index
+    IndexNotFound,
+    Failed(Error),
+}
+
+/// Network failures and internal Meilisearch errors may succeed when tried again
+fn is_transient(e: &Error) -> bool {
+    matches!(e, Error::UnreachableServer | Error::HttpError(_)
+        | Error::Meilisearch(MeilisearchError { error_type: ErrorType::Internal, .. }))
+}
+
+/// Runs the search under the configured timeout, retrying once on transient errors
+async fn execute_search<T: DeserializeOwned + 'static>(search: &MeiliSearchQuery<'_>, app: &AppState)
+    -> std::result::Result<SearchResults<T>, SearchFailure> {
+    let mut retried = false;
+    loop {
+        match timeout(app.meilisearch_timeout, search.execute::<T>()).await {
+            Ok(Ok(results)) => return Ok(results),
+            Ok(Err(e)) if is_transient(&e) && !retried => {
+                warn!(target: "meilisearch", "Search failed, retrying once: {e:?}");
+                retried = true;
+            },
+            Ok(Err(Error::Meilisearch(MeilisearchError { error_code: ErrorCode::IndexNotFound, .. }))) =>
+                return Err(SearchFailure::IndexNotFound),
+            Ok(Err(e)) => return Err(SearchFailure::Failed(e)),
+            Err(_) => return Err(SearchFailure::TimedOut)
+        }
+    }
+}
+
+/// Creates the missing index through a sync so that searching works again once it is done
+async fn missing_index_response(app: &Data<AppState>) -> HttpResponse {
+    warn!(target: "meilisearch", "Index `{ANIMES_INDEX}` does not exist, starting a sync");
+    match start_sync(app).await {
+        Ok(Some(job)) => info!(target: "meilisearch", "Started sync job `{}`", job.id),
+        Ok(None) => {},
+        Err(e) => error!("Could not start sync: {e:?}")
+    }
+    KError::service_unavailable("The search index is being created, please try again shortly")
+}
+
+async fn search_failure_response(failure: SearchFailure, app: &Data<AppState>) -> HttpResponse {
+    match failure {
+        SearchFailure::TimedOut => warn!(target: "meilisearch", "Search timed out"),
+        SearchFailure::IndexNotFound => return missing_index_response(app).await,
+        SearchFailure::Failed(e) => error!("Could not search: {e:?}")
+    }
+    KError::internal_error("Could not perform search")
+}
+
+fn escape_regex(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if "\\.+*?()|[]{}^$#-".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Plain title matching against MongoDB, only used while Meilisearch cannot answer
+async fn fallback_search(query: &SearchQuery, offset: u32, limit: u32, app: &AppState)
+    -> Result<Vec<AnimeSeriesSearchEntry>> {
+    let collection: mongodb::Collection<WithOID<AnimeSeries>> =
+        app.mongodb.database(DB_NAME).collection(COLL_NAME);
+    let pattern = bson::Regex { pattern: escape_regex(&query.query), options: "i".to_string() };
+    let mut filter = doc! {
+        "$or": [
+            { "titles.value": &pattern },
+            // legacy documents store plain strings
+            { "titles": &pattern }
+        ]
+    };
+    if let Some(kind) = query.kind.as_deref().and_then(SeasonKind::parse) {
+        filter.insert("mapping.kind", kind.as_str());
+    }
+    let options = FindOptions::builder()
+        .sort(doc! { "_id": 1 })
+        .skip(offset as u64)
+        .limit(limit.min(ANIMES_SEARCH_FALLBACK_LIMIT) as i64)
+        .build();
+    let animes: Vec<WithOID<AnimeSeries>> = collection.find(filter, options).await
+        .context("Finding animes matching the query")?
+        .try_collect().await
+        .context("Collecting animes matching the query")?;
+    Ok(animes.into_iter()
+        .map(|anime| WithID::from(anime).into())
+        .collect())
+}
+
+/// Mods and admins are allowed larger result pages than everyone else
+fn search_soft_limit(session: Option<&Session>, app: &AppState) -> u32 {
+    match session {
+        Some(session) if session.role >= Role::Mod => app.search.privileged_soft_limit(),
+        _ => app.search.soft_limit()
+    }
+}
+
+fn build_search<'a>(index: &'a Index, query: &'a SearchQuery, sort: &'a [&'a str],
+    offset: usize, limit: usize) -> MeiliSearchQuery<'a> {
+    let mut search = index.search();
+    search.with_query(&query.query)
+        .with_offset(offset)
+        .with_limit(limit)
+        .with_show_matches_position(query.display_matches);
+    if !sort.is_empty() {
+        search.with_sort(sort);
+    }
+    if let Some(filter) = &query.filter {
+        search.with_filter(filter);
+    }
+    if query.highlight {
+        search.with_attributes_to_highlight(Selectors::Some(&["titles"]));
+    }
+    search
+}
+
+fn wants_ndjson(req: &HttpRequest) -> bool {
+    req.headers().get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .map(|accept| accept.contains(NDJSON_CONTENT_TYPE))
+        .unwrap_or(false)
+}
+
+struct SearchStream {
+    query: SearchQuery,
+    app: Data<AppState>,
+    offset: usize,
+    end: usize,
+}
+
+/// Streams one entry per line, paging through Meilisearch until `end` or the last result
+fn stream_search(state: SearchStream) -> impl Stream<Item = Result<Bytes, actix_web::Error>> {
+    futures::stream::unfold(Some(state), |state| async move {
+        let mut state = state?;
+        if state.offset >= state.end {
+            return None;
+        }
+        let limit = ANIMES_SEARCH_STREAM_PAGE_SIZE.min(state.end - state.offset);
+        let index = state.app.meilisearch.index(ANIMES_INDEX);
+        let sort: Vec<&str> = state.query.sort.iter().map(String::as_str).collect();
+        let search = build_search(&index, &state.query, &sort, state.offset, limit);
+        let results = execute_search::<AnimeSeriesSearchEntry>(&search, &state.app).await;
+        drop(search);
+        match results {
+            Ok(results) => {
+                let count = results.hits.len();
+                let mut page = Vec::new();
+                for hit in results.hits {
+                    let entry: AnimeSeriesSearchEntry = hit.into();
+                    serde_json::to_writer(&mut page, &entry)
+                        .expect("Search entries should always be serializable");
+                    page.push(b'\n');
+                }
+                state.offset += count;
+                let next = if count < limit { None } else { Some(state) };
+                Some((Ok(Bytes::from(page)), next))
+            },
+            Err(failure) => {
+                match failure {
+                    SearchFailure::TimedOut => warn!(target: "meilisearch", "Streamed search timed out"),
+                    SearchFailure::IndexNotFound => warn!(target: "meilisearch", "Index `{ANIMES_INDEX}` does not exist"),
+                    SearchFailure::Failed(e) => error!("Could not stream search: {e:?}")
+                }
+                Some((Err(actix_web::error::ErrorInternalServerError("Could not perform search")), None))
+            }
+        }
+    })
 }
 
-async fn search_animes(query: SearchQuery, app: Data<AppState>) -> HttpResponse {
+async fn search_animes(mut query: SearchQuery, session: Option<ReqData<Session>>,
+    app: Data<AppState>, ndjson: bool) -> HttpResponse {
+    query.normalize();
     if !query.validate() {
         return KError::bad_request("Query length must be between 2 and 128 characters");
     }
+    let offset = query.offset.unwrap_or(0);
+    if offset >= ANIMES_SEARCH_MAX_OFFSET {
+        return KError::bad_request(&format!("Offset must be lower than {ANIMES_SEARCH_MAX_OFFSET}"));
+    }
+    if !query.validate_sort() {
+        return KError::bad_request("Sort must be at most two of releaseYear:asc, releaseYear:desc, titles:asc or titles:desc");
+    }
+    if !query.parse_kind() {
+        return unknown_kind_response();
+    }
+    let limit = query.limit.unwrap_or(app.search.default_limit())
+        .min(search_soft_limit(session.as_deref(), &app));
+    if ndjson {
+        // the fallback cannot be streamed, better to fail now than after the headers are sent
+        if app.meilisearch_health.is_known_down() {
+            return KError::service_unavailable("Search is temporarily unavailable");
+        }
+        let end = offset.saturating_add(limit).min(ANIMES_SEARCH_MAX_OFFSET);
+        info!("Streaming results for `{}`", query.query);
+        return HttpResponse::Ok()
+            .content_type(NDJSON_CONTENT_TYPE)
+            .streaming(stream_search(SearchStream {
+                query,
+                app,
+                offset: offset as usize,
+                end: end as usize
+            }));
+    }
+    if app.meilisearch_health.is_known_down() {
+        return degraded_search_response(&query, offset, limit, &app).await;
+    }
+    let sort: Vec<&str> = query.sort.iter().map(String::as_str).collect();
 
-    let results = app.meilisearch
-        .index(ANIMES_INDEX)
-        .search()
-        .with_query(&query.query)
-        .with_offset(query.offset.unwrap_or(0) as usize)
-        .with_limit(query.limit.unwrap_or(ANIMES_SEARCH_DEFAULT_LIMIT)
-            .min(ANIMES_SEARCH_SOFT_LIMIT) as usize)
-        .with_show_matches_position(query.display_matches)
-        .execute()
-        .await;
+    let index = app.meilisearch.index(ANIMES_INDEX);
+    let search = build_search(&index, &query, &sort, offset as usize, limit as usize);
+    let results = execute_search(&search, &app).await;
 
     match results {
-        Ok(docs) => {
-            let docs: Vec<AnimeSeriesSearchEntry> = docs.hits.into_iter()
+        Ok(results) => {
+            let estimated_total = results.estimated_total_hits.unwrap_or(0);
+            let reachable = estimated_total.min(ANIMES_SEARCH_MAX_OFFSET as usize);
+            let docs: Vec<AnimeSeriesSearchEntry> = results.hits.into_iter()
                 .map(|r| r.into()).collect();
             info!("Found {} results for `{}`", docs.len(), query.query);
-            HttpResponse::Ok().json(docs)
+
+            let next_offset = offset as usize + docs.len();
+            let has_more = !docs.is_empty() && next_offset < reachable;
+            let out_of_range = offset > 0 && offset as usize >= estimated_total;
+            let mut res = HttpResponse::Ok();
+            res.insert_header(cache_control(&app, CacheKind::Search));
+            res.insert_header((HAS_MORE_HEADER, has_more.to_string()));
+            res.insert_header((ESTIMATED_TOTAL_HITS_HEADER, estimated_total.to_string()));
+            res.insert_header((OFFSET_OUT_OF_RANGE_HEADER, out_of_range.to_string()));
+            if has_more {
+                res.insert_header((NEXT_OFFSET_HEADER, next_offset.to_string()));
+            }
+            res.json(docs)
+        }
+        Err(SearchFailure::IndexNotFound) => missing_index_response(&app).await,
+        Err(failure) => {
+            // only the periodic health check is relied on beforehand, between two checks a failed
+            // search tells us just as well
+            match failure {
+                SearchFailure::TimedOut => warn!(target: "meilisearch", "Search timed out, falling back to MongoDB"),
+                SearchFailure::IndexNotFound => unreachable!("Handled above"),
+                SearchFailure::Failed(e) => error!("Could not search, falling back to MongoDB: {e:?}")
+            }
+            degraded_search_response(&query, offset, limit, &app).await
         }
+    }
+}
+
+async fn degraded_search_response(query: &SearchQuery, offset: u32, limit: u32, app: &AppState) -> HttpResponse {
+    match fallback_search(query, offset, limit, app).await {
+        Ok(docs) => HttpResponse::Ok()
+            .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+            .insert_header((SEARCH_DEGRADED_HEADER, "true"))
+            .insert_header((HAS_MORE_HEADER, "false"))
+            .json(docs),
         Err(e) => {
-            error!("Could not search: {e:?}");
+            error!("Could not perform fallback search: {e:?}");
             KError::internal_error("Could not perform search")
         }
     }
 }
 
-pub async fn search_anime_form(form: Form<SearchQuery>, app: Data<AppState>) -> impl Responder {
-    search_animes(form.into_inner(), app).await
+#[derive(Deserialize, Debug, Clone)]
+pub struct SuggestQuery {
+    query: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct SuggestHit {
+    id: String,
+    #[serde(default)]
+    titles: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+struct Suggestion {
+    id: String,
+    title: String,
+}
+
+pub async fn suggest_animes(query: Query<SuggestQuery>, app: Data<AppState>) -> impl Responder {
+    let query = normalize_query(&query.query);
+    let len = query.chars().count();
+    if !(ANIMES_SUGGEST_QUERY_MIN_LEN..=ANIMES_SEARCH_QUERY_MAX_LEN).contains(&len) {
+        return KError::bad_request("Query length must be between 1 and 128 characters");
+    }
+    let index = app.meilisearch.index(ANIMES_INDEX);
+    let mut search = index.search();
+    search.with_query(&query)
+        .with_limit(ANIMES_SUGGEST_LIMIT)
+        .with_attributes_to_retrieve(Selectors::Some(&["id", "titles"]));
+    let results = execute_search::<SuggestHit>(&search, &app).await;
+    match results {
+        Ok(results) => {
+            // search entries store the primary title first
+            let suggestions: Vec<Suggestion> = results.hits.into_iter()
+                .filter_map(|hit| {
+                    let title = hit.result.titles.into_iter().next()?;
+                    Some(Suggestion { id: hit.result.id, title })
+                })
+                .collect();
+            HttpResponse::Ok()
+                .insert_header(cache_control(&app, CacheKind::Search))
+                .json(suggestions)
+        }
+        Err(failure) => search_failure_response(failure, &app).await
+    }
+}
+
+pub async fn search_anime_form(req: HttpRequest, form: Form<SearchQuery>,
+    session: Option<ReqData<Session>>, app: Data<AppState>) -> impl Responder {
+    search_animes(form.into_inner(), session, app, wants_ndjson(&req)).await
 }
 
-pub async fn search_anime_json(json: Json<SearchQuery>, app: Data<AppState>) -> impl Responder {
-    search_animes(json.into_inner(), app).await
+pub async fn search_anime_json(req: HttpRequest, json: Json<SearchQuery>,
+    session: Option<ReqData<Session>>, app: Data<AppState>) -> impl Responder {
+    search_animes(json.into_inner(), session, app, wants_ndjson(&req)).await
 }
 
 async fn find_anime(anime_id: &ObjectId, app: &AppState) -> Result<Option<WithOID<AnimeSeries>>> {
@@ -146,15 +849,80 @@ async fn find_anime(anime_id: &ObjectId, app: &AppState) -> Result<Option<WithOI
         .await.context("Finding anime with the specified ID")
 }
 
-#[get("/anime/{id}")]
-pub async fn fetch_anime_details(path: Path<String>, app: Data<AppState>) -> impl Responder {
-    let Some(anime_id) = to_oid(&path.into_inner()) else {
-        return KError::bad_request("The provided ID is not valid");
+#[derive(Deserialize, Debug, Clone)]
+pub struct DetailsQuery {
+    fields: Option<String>,
+}
+
+fn parse_projection(fields: &str) -> Option<bson::Document> {
+    let mut projection = bson::Document::new();
+    for field in fields.split(',').map(str::trim) {
+        if !ANIME_SERIES_FIELDS.contains(&field) {
+            return None;
+        }
+        projection.insert(field, 1);
+    }
+    Some(projection)
+}
+
+async fn find_anime_projected(anime_id: &ObjectId, projection: bson::Document, app: &AppState)
+    -> Result<Option<bson::Document>> {
+    let collection: mongodb::Collection<bson::Document> = app.mongodb.database(DB_NAME)
+        .collection(COLL_NAME);
+    collection.find_one(doc! { "_id": anime_id }, FindOneOptions::builder()
+            .projection(projection).build())
+        .await.context("Finding projected anime with the specified ID")
+}
+
+/// Rewrites legacy string titles of a projected document to the shape of [`Title`], the one
+/// the full details are returned with
+fn normalize_projected_titles(anime: &mut bson::Document) -> Result<()> {
+    if let Some(titles) = anime.get("titles") {
+        let titles: Vec<Title> = bson::from_bson(titles.clone()).context("Reading projected titles")?;
+        anime.insert("titles", bson::to_bson(&titles)?);
+    }
+    Ok(())
+}
+
+async fn fetch_projected_anime_details(anime_id: ObjectId, fields: &str, app: &AppState) -> HttpResponse {
+    let Some(projection) = parse_projection(fields) else {
+        return KError::bad_request("Unknown field name in `fields`");
+    };
+    match find_anime_projected(&anime_id, projection, app).await {
+        Ok(Some(mut anime)) => {
+            if let Err(e) = normalize_projected_titles(&mut anime) {
+                error!("Could not read anime titles: {e:?}");
+                return KError::db_error();
+            }
+            anime.remove("_id");
+            anime.insert("id", anime_id.to_hex());
+            HttpResponse::Ok()
+                .insert_header(cache_control(app, CacheKind::Details))
+                .json(anime)
+        },
+        Ok(None) => KError::not_found(),
+        Err(e) => {
+            error!("Could not find anime: {e:?}");
+            KError::db_error()
+        }
+    }
+}
+
+pub async fn fetch_anime_details(path: Path<String>, query: Query<DetailsQuery>,
+    app: Data<AppState>) -> impl Responder {
+    let anime_id = match to_oid(&path.into_inner()) {
+        Ok(anime_id) => anime_id,
+        Err(e) => return KError::bad_request(&e.to_string())
     };
+    if let Some(fields) = &query.fields {
+        return fetch_projected_anime_details(anime_id, fields, &app).await;
+    }
     match find_anime(&anime_id, &app).await {
         Ok(Some(anime)) => {
             let renamed: WithID<AnimeSeries> = anime.into();
-            HttpResponse::Ok().json(renamed)
+            HttpResponse::Ok()
+                .insert_header(cache_control(&app, CacheKind::Details))
+                .json(renamed)
         },
         Ok(None) => KError::not_found(),
         Err(e) => {
@@ -164,73 +932,885 @@ pub async fn fetch_anime_details(path: Path<String>, app: Data<AppState>) -> imp
     }
 }
 
-async fn send_anime_to_meili(anime: AnimeSeriesSearchEntry, app: &AppState) -> Result<()> {
-    app.meilisearch.get_index(ANIMES_INDEX)
-        .await?
-        .add_or_replace(&[anime], Some(ANIME_PRIMARY_KEY))
-        .await?
-        .wait_for_completion(&app.meilisearch, None, None)
-        .await?;
-    Ok(())
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ListQuery {
+    updated_since: Option<u64>,
+    /// Inclusive lower bound of `createdOn`, in milliseconds
+    created_after: Option<u64>,
+    /// Exclusive upper bound of `createdOn`, in milliseconds
+    created_before: Option<u64>,
+    sort: Option<String>,
+    offset: Option<u64>,
+    limit: Option<u32>,
 }
 
-#[derive(MultipartForm)]
-struct AnimeMultipartCandidate {
-    candidate: actix_easy_multipart::json::Json<AnimeSeriesCandidate>,
-    poster: Tempfile,
+async fn list_animes(query: &ListQuery, sort: (ListSort, i32), app: &AppState)
+    -> Result<Vec<WithID<AnimeSeries>>> {
+    let collection: mongodb::Collection<WithOID<AnimeSeries>> =
+        app.mongodb.database(DB_NAME).collection(COLL_NAME);
+    let mut filter = bson::Document::new();
+    if let Some(since) = query.updated_since {
+        filter.insert("updatedOn", doc! { "$gt": since as i64 });
+    }
+    let mut created = bson::Document::new();
+    if let Some(after) = query.created_after {
+        created.insert("$gte", after as i64);
+    }
+    if let Some(before) = query.created_before {
+        created.insert("$lt", before as i64);
+    }
+    if !created.is_empty() {
+        filter.insert("createdOn", created);
+    }
+    let limit = query.limit.unwrap_or(ANIMES_LIST_DEFAULT_LIMIT)
+        .min(ANIMES_LIST_SOFT_LIMIT);
+    let options = FindOptions::builder()
+        .sort(list_sort_document(sort))
+        .skip(query.offset)
+        .limit(limit as i64)
+        .build();
+    let animes: Vec<WithOID<AnimeSeries>> = collection.find(filter, options).await
+        .context("Listing animes")?
+        .try_collect().await
+        .context("Collecting listed animes")?;
+    Ok(animes.into_iter().map(Into::into).collect())
 }
 
-async fn push_anime(form: MultipartForm<AnimeMultipartCandidate>, app: Data<AppState>) -> HttpResponse {
-    let form = form.into_inner();
-    let mut anime = {
-        let key: String = random_string::generate(20, CACHE_KEY_ALPHABET);
-        let candidate = form.candidate.into_inner();
-        candidate.into_anime(CachedImage::new(key))
-    };
+/// The `_id` keeps the order stable between pages when several documents share a value
+fn list_sort_document((sort, direction): (ListSort, i32)) -> bson::Document {
+    doc! { sort.field: direction, "_id": direction }
+}
 
-    let poster = form.poster;
-    match poster.content_type.as_ref().map(AsRef::as_ref) {
-        // TODO: Add support for other types of images
-        Some("image/webp") /*| Some("image/png")*/ => {
-            match export_poster(anime.poster.key().to_string(), poster.file.path(), &app.cache_folder) {
-                Ok(ci) => {
-                    anime.poster = ci;
-                    export_presenter(&anime, &app.cache_folder)
-                        .unwrap_or_else(|_| warn!("Could not generate presenter"));
-                },
-                Err(e) => {
-                    error!("Could not export poster: {e:?}");
-                    poster.file.close().unwrap_or_else(|_| warn!("Could not delete temp file"));
-                    return KError::internal_error("Could not generate image set")
-                }
-            }
-            poster.file.close().unwrap_or_else(|_| warn!("Could not delete temp file"));
-        },
-        _ => {
-            poster.file.close().unwrap_or_else(|_| warn!("Could not delete temp file"));
-            return KError::bad_request("Only webp images are supported")
+pub async fn fetch_anime_list(query: Query<ListQuery>, app: Data<AppState>) -> impl Responder {
+    let Some(sort) = parse_list_sort(query.sort.as_deref().unwrap_or(app.list.default_sort())) else {
+        let names: Vec<&str> = ANIME_LIST_SORTS.iter().map(|sort| sort.name).collect();
+        return KError::bad_request(&format!("Sort must be one of {}, optionally prefixed with `-`",
+            names.join(", ")))
+    };
+    if matches!((query.created_after, query.created_before), (Some(after), Some(before)) if after > before) {
+        return KError::bad_request("CreatedAfter must not be greater than createdBefore");
+    }
+    match list_animes(&query, sort, &app).await {
+        Ok(animes) => HttpResponse::Ok().json(animes),
+        Err(e) => {
+            error!("Could not list animes: {e:?}");
+            KError::db_error()
         }
     }
+}
 
-    let collection: mongodb::Collection<AnimeSeries> =
+#[derive(Deserialize, Debug, Clone)]
+pub struct RecentQuery {
+    /// `updated` or `created`, defaults to `updated`
+    by: Option<String>,
+    limit: Option<u32>,
+}
+
+async fn find_recent_animes(field: &str, limit: u32, app: &AppState) -> Result<Vec<AnimeSeriesSearchEntry>> {
+    let collection: mongodb::Collection<WithOID<AnimeSeries>> =
         app.mongodb.database(DB_NAME).collection(COLL_NAME);
-    match collection.insert_one(&anime, None).await {
-        Ok(InsertOneResult { inserted_id, .. }) => {
-            let inserted_id = inserted_id.as_object_id()
-                .expect("Value must be ObjectId").to_hex();
-            let anime = WithID::new(inserted_id, anime);
-            if let Err(e) = send_anime_to_meili(anime.clone().into(), &app).await {
-                warn!("Could not add pushed anime to meilisearch: {e:?}");
-            }
-            // TODO: Maybe try to not rebuild everything but just add the new anime
-            if let Err(e) = seo::build_sitemap(&app).await {
-                warn!("Could not rebuild sitemap: {e:?}");
-            }
-            HttpResponse::Created().json(anime)
-        },
+    let options = FindOptions::builder()
+        .sort(doc! { field: -1, "_id": -1 })
+        .limit(limit as i64)
+        .build();
+    let animes: Vec<WithOID<AnimeSeries>> = collection.find(None, options).await
+        .context("Finding recent animes")?
+        .try_collect().await
+        .context("Collecting recent animes")?;
+    Ok(animes.into_iter().map(Into::into).collect())
+}
+
+/// Latest edited or added animes first, in the same shape as the search results
+pub async fn fetch_recent_animes(query: Query<RecentQuery>, app: Data<AppState>) -> impl Responder {
+    let field = match query.by.as_deref() {
+        None | Some("updated") => "updatedOn",
+        Some("created") => "createdOn",
+        Some(_) => return KError::bad_request("By must be one of updated, created")
+    };
+    let limit = query.limit.unwrap_or(ANIMES_RECENT_DEFAULT_LIMIT)
+        .min(ANIMES_RECENT_SOFT_LIMIT);
+    match find_recent_animes(field, limit, &app).await {
+        Ok(animes) => HttpResponse::Ok()
+            .insert_header(cache_control(&app, CacheKind::Search))
+            .json(animes),
         Err(e) => {
-            // TODO: delete generated poster files
-            error!("Could not push anime to db: {e:?}");
+            error!("Could not find recent animes: {e:?}");
+            KError::db_error()
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct PreviewQuery {
+    w: Option<u32>,
+    h: Option<u32>,
+}
+
+pub async fn fetch_anime_preview(path: Path<String>, query: Query<PreviewQuery>,
+    app: Data<AppState>) -> impl Responder {
+    let anime_id = match to_oid(&path.into_inner()) {
+        Ok(anime_id) => anime_id,
+        Err(e) => return KError::bad_request(&e.to_string())
+    };
+    let width = query.w.unwrap_or(PREVIEW_DEFAULT_WIDTH).clamp(1, PREVIEW_MAX_WIDTH);
+    let height = query.h.unwrap_or(PREVIEW_DEFAULT_HEIGHT).clamp(1, PREVIEW_MAX_HEIGHT);
+    let anime = match find_anime(&anime_id, &app).await {
+        Ok(Some(anime)) => anime,
+        Ok(None) => return KError::not_found(),
+        Err(e) => {
+            error!("Could not find anime: {e:?}");
+            return KError::db_error();
+        }
+    };
+    let Some(placeholder) = anime.as_ref().poster.placeholder() else {
+        return KError::not_found();
+    };
+    match render_placeholder_preview(placeholder, width, height) {
+        Ok(png) => HttpResponse::Ok()
+            .content_type("image/png")
+            .insert_header(cache_control(&app, CacheKind::Details))
+            .body(png),
+        Err(e) => {
+            error!("Could not render placeholder preview: {e:?}");
+            KError::internal_error("Could not render preview")
+        }
+    }
+}
+
+pub async fn fetch_anime_presenter(req: HttpRequest, path: Path<String>, app: Data<AppState>) -> impl Responder {
+    let anime_id = match to_oid(&path.into_inner()) {
+        Ok(anime_id) => anime_id,
+        Err(e) => return KError::bad_request(&e.to_string())
+    };
+    let anime = match find_anime(&anime_id, &app).await {
+        Ok(Some(anime)) => anime,
+        Ok(None) => return KError::not_found(),
+        Err(e) => {
+            error!("Could not find anime: {e:?}");
+            return KError::db_error();
+        }
+    };
+    let presenter = get_presenter_path(anime.as_ref().poster.key(), &app.cache_folder);
+    match web::block(move || std::fs::read(presenter)).await {
+        Ok(Ok(image)) => {
+            let mut res = HttpResponse::Ok();
+            res.content_type("image/webp")
+                .insert_header(cache_control(&app, CacheKind::Presenter));
+            serve_image(&req, res, image)
+        },
+        Ok(Err(e)) if e.kind() == std::io::ErrorKind::NotFound => match &app.images.fallback_poster {
+            // at full resolution, the closest to the size of a presenter
+            Some(fallback) => serve_fallback_poster(&req, fallback.clone(), ANIME_POSTER_SIZES[0],
+                PosterFormat::WebP, &app).await,
+            None => KError::not_found()
+        },
+        Ok(Err(e)) => {
+            error!("Could not read presenter image: {e:?}");
+            KError::internal_error("Could not read presenter image")
+        },
+        Err(e) => {
+            error!("Could not read presenter image: {e:?}");
+            KError::internal_error("Could not read presenter image")
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct PageQuery {
+    offset: Option<u64>,
+    limit: Option<u32>,
+}
+
+/// Case-insensitive comparisons, the creator indexes are built with the same collation
+fn case_insensitive_collation() -> Collation {
+    Collation::builder()
+        .locale("en")
+        .strength(CollationStrength::Secondary)
+        .build()
+}
+
+async fn list_animes_by(field: &str, value: &str, page: &PageQuery, app: &AppState)
+    -> Result<Vec<WithID<AnimeSeries>>> {
+    let collection: mongodb::Collection<WithOID<AnimeSeries>> =
+        app.mongodb.database(DB_NAME).collection(COLL_NAME);
+    let limit = page.limit.unwrap_or(ANIMES_LIST_DEFAULT_LIMIT)
+        .min(ANIMES_LIST_SOFT_LIMIT);
+    let options = FindOptions::builder()
+        .collation(case_insensitive_collation())
+        .sort(doc! { "_id": 1 })
+        .skip(page.offset)
+        .limit(limit as i64)
+        .build();
+    let animes: Vec<WithOID<AnimeSeries>> = collection.find(doc! { field: value }, options).await
+        .with_context(|| format!("Listing animes by `{field}`"))?
+        .try_collect().await
+        .with_context(|| format!("Collecting animes listed by `{field}`"))?;
+    Ok(animes.into_iter().map(Into::into).collect())
+}
+
+async fn respond_with_animes_by(field: &str, value: &str, page: &PageQuery, app: &AppState)
+    -> HttpResponse {
+    match list_animes_by(field, value, page, app).await {
+        Ok(animes) => HttpResponse::Ok()
+            .insert_header(cache_control(app, CacheKind::Details))
+            .json(animes),
+        Err(e) => {
+            error!("Could not list animes: {e:?}");
+            KError::db_error()
+        }
+    }
+}
+
+pub async fn fetch_animes_by_studio(path: Path<String>, page: Query<PageQuery>,
+    app: Data<AppState>) -> impl Responder {
+    respond_with_animes_by("anime.studios", &path.into_inner(), &page, &app).await
+}
+
+pub async fn fetch_animes_by_author(path: Path<String>, page: Query<PageQuery>,
+    app: Data<AppState>) -> impl Responder {
+    respond_with_animes_by("manga.author", &path.into_inner(), &page, &app).await
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct LocateQuery {
+    episode: Option<u16>,
+    chapter: Option<u16>,
+    volume: Option<u16>,
+}
+
+pub async fn locate_in_anime(path: Path<String>, query: Query<LocateQuery>,
+    app: Data<AppState>) -> impl Responder {
+    let anime_id = match to_oid(&path.into_inner()) {
+        Ok(anime_id) => anime_id,
+        Err(e) => return KError::bad_request(&e.to_string())
+    };
+    let (unit, n) = match (query.episode, query.chapter, query.volume) {
+        (Some(n), None, None) => (MappingUnit::Episode, n),
+        (None, Some(n), None) => (MappingUnit::Chapter, n),
+        (None, None, Some(n)) => (MappingUnit::Volume, n),
+        _ => return KError::bad_request("Exactly one of `episode`, `chapter` or `volume` is expected")
+    };
+    match find_anime(&anime_id, &app).await {
+        Ok(Some(anime)) => match locate_in_mapping(&anime.as_ref().mapping, unit, n) {
+            Some(location) => HttpResponse::Ok()
+                .insert_header(cache_control(&app, CacheKind::Details))
+                .json(location),
+            None => KError::not_found()
+        },
+        Ok(None) => KError::not_found(),
+        Err(e) => {
+            error!("Could not find anime: {e:?}");
+            KError::db_error()
+        }
+    }
+}
+
+/// Fills `normalizedTitles` on the documents written before it existed, legacy documents store
+/// their titles as plain strings
+async fn backfill_normalized_titles(mongodb: &Client) -> Result<()> {
+    let collection: mongodb::Collection<bson::Document> =
+        mongodb.database(DB_NAME).collection(COLL_NAME);
+    let mut cur = collection
+        .find(doc! { "normalizedTitles": { "$exists": false } },
+            FindOptions::builder().projection(doc! { "titles": 1 }).build())
+        .await
+        .context("Finding animes without normalized titles")?;
+    let mut count = 0;
+    while let Some(anime) = cur.try_next().await.context("Reading animes without normalized titles")? {
+        let Ok(id) = anime.get_object_id("_id") else {
+            continue;
+        };
+        let normalized: Vec<String> = anime.get_array("titles").map(Vec::as_slice).unwrap_or_default()
+            .iter()
+            .filter_map(|title| match title {
+                bson::Bson::String(value) => Some(value.as_str()),
+                bson::Bson::Document(title) => title.get_str("value").ok(),
+                _ => None
+            })
+            .map(normalize_title)
+            .collect();
+        collection.update_one(doc! { "_id": id }, doc! { "$set": { "normalizedTitles": normalized } }, None)
+            .await
+            .context("Saving normalized titles")?;
+        count += 1;
+    }
+    if count > 0 {
+        info!(target: "mongodb", "Backfilled normalized titles of {count} animes");
+    }
+    Ok(())
+}
+
+fn external_id_index(source: &str) -> String {
+    format!("externalIds.{source}_unique")
+}
+
+/// Replaces the sparse indexes on the external ids by unique ones, they cannot be created while
+/// two animes share an id
+async fn ensure_unique_external_ids(collection: &mongodb::Collection<AnimeSeries>, existing: &[String]) -> Result<()> {
+    for source in EXTERNAL_ID_SOURCES {
+        let field = format!("externalIds.{source}");
+        let legacy = format!("{field}_1");
+        if existing.contains(&legacy) {
+            collection.drop_index(&legacy, None).await
+                .with_context(|| format!("Dropping the non-unique index {legacy}"))?;
+        }
+        let name = external_id_index(source);
+        let index = IndexModel::builder()
+            .keys(doc! { &field: 1 })
+            .options(IndexOptions::builder()
+                .name(name.clone())
+                .unique(true)
+                .partial_filter_expression(doc! { &field: { "$exists": true } })
+                .build())
+            .build();
+        collection.create_index(index, None).await
+            .with_context(|| format!("Creating the unique index on {field}, some animes may share an id"))?;
+        if !existing.contains(&name) {
+            info!(target: "mongodb", "Created index {name}");
+        }
+    }
+    Ok(())
+}
+
+/// The source of the external id that made the write fail because another anime has it
+fn duplicate_external_id(e: &mongodb::error::Error) -> Option<&'static str> {
+    use mongodb::error::{ErrorKind, WriteFailure};
+    match &*e.kind {
+        ErrorKind::Write(WriteFailure::WriteError(e)) if e.code == MONGODB_DUPLICATE_KEY =>
+            EXTERNAL_ID_SOURCES.into_iter().find(|source| e.message.contains(&external_id_index(source))),
+        _ => None
+    }
+}
+
+fn external_id_conflict_details(source: &str) -> String {
+    format!("This {source} id is already claimed by another anime")
+}
+
+/// Answers a write rejected by the unique index on `source`, along with the anime holding the id
+async fn external_id_conflict(source: &str, external_ids: Option<&ExternalIds>, except: Option<&ObjectId>,
+                              app: &AppState) -> HttpResponse {
+    let details = external_id_conflict_details(source);
+    let id = external_ids.and_then(|ids| ids.entries().into_iter()
+        .find(|(s, _)| *s == source)
+        .and_then(|(_, id)| id));
+    let Some(id) = id else {
+        return KError::conflict(&details);
+    };
+    match find_external_id_owner(source, id, except, app).await {
+        Ok(Some(owner)) => KError::conflict_with(&details, &owner),
+        Ok(None) => KError::conflict(&details),
+        Err(e) => {
+            warn!("Could not find the anime claiming the external id: {e:?}");
+            KError::conflict(&details)
+        }
+    }
+}
+
+/// Whether the write was rejected because an anime already has the same `_id`
+fn is_duplicate_id(e: &mongodb::error::Error) -> bool {
+    use mongodb::error::{ErrorKind, WriteFailure};
+    matches!(&*e.kind, ErrorKind::Write(WriteFailure::WriteError(e))
+        if e.code == MONGODB_DUPLICATE_KEY && e.message.contains("index: _id_ "))
+}
+
+/// Whether the write was rejected by the unique index on the normalized titles
+fn is_duplicate_title(e: &mongodb::error::Error) -> bool {
+    use mongodb::error::{ErrorKind, WriteFailure};
+    matches!(&*e.kind, ErrorKind::Write(WriteFailure::WriteError(e))
+        if e.code == MONGODB_DUPLICATE_KEY && e.message.contains(UNIQUE_TITLES_INDEX))
+}
+
+/// Backfills and creates the unique index on the normalized titles, it cannot be created while
+/// duplicates remain. Untitled animes are left out of it, they never share a title.
+async fn ensure_unique_titles(collection: &mongodb::Collection<AnimeSeries>, mongodb: &Client,
+                              existing: &[String]) -> Result<()> {
+    backfill_normalized_titles(mongodb).await?;
+    if existing.iter().any(|name| name == LEGACY_UNIQUE_TITLES_INDEX) {
+        collection.drop_index(LEGACY_UNIQUE_TITLES_INDEX, None).await
+            .with_context(|| format!("Dropping the sparse index {LEGACY_UNIQUE_TITLES_INDEX}"))?;
+    }
+    let index = IndexModel::builder()
+        .keys(doc! { "normalizedTitles": 1 })
+        .options(IndexOptions::builder()
+            .name(UNIQUE_TITLES_INDEX.to_string())
+            .unique(true)
+            .partial_filter_expression(doc! { "normalizedTitles": { "$type": "string" } })
+            .collation(case_insensitive_collation())
+            .build())
+        .build();
+    collection.create_index(index, None).await
+        .context("Creating the unique index on normalized titles, some animes may share a title")?;
+    Ok(())
+}
+
+/// Creates the MongoDB indexes the lookup and list endpoints rely on, creating an existing index
+/// is a no-op
+pub async fn ensure_indexes(mongodb: &Client, catalog: &CatalogConfig) -> Result<()> {
+    let collection: mongodb::Collection<AnimeSeries> =
+        mongodb.database(DB_NAME).collection(COLL_NAME);
+    let existing = match collection.list_index_names().await {
+        Ok(names) => names,
+        // the collection does not exist yet
+        Err(e) if matches!(*e.kind, mongodb::error::ErrorKind::Command(ref c) if c.code == 26) => vec![],
+        Err(e) => return Err(e).context("Listing existing indexes")
+    };
+    // descending sorts walk the same indexes backwards
+    let indexes = ANIME_LIST_SORTS.into_iter().map(|sort| IndexModel::builder()
+            .keys(list_sort_document((sort, 1)))
+            .build())
+        .chain(std::iter::once(IndexModel::builder()
+            .keys(doc! { "mapping.kind": 1 })
+            .build()))
+        .chain(std::iter::once(IndexModel::builder()
+            .keys(doc! { "slug": 1 })
+            .options(IndexOptions::builder().unique(true).sparse(true).build())
+            .build()))
+        .chain(["anime.studios", "manga.author"].into_iter().map(|field| IndexModel::builder()
+            .keys(doc! { field: 1 })
+            .options(IndexOptions::builder().collation(case_insensitive_collation()).build())
+            .build()));
+    let res = collection.create_indexes(indexes, None).await
+        .context("Creating lookup indexes")?;
+    let created: Vec<&str> = res.index_names.iter()
+        .map(String::as_str)
+        .filter(|name| !existing.iter().any(|e| e == name))
+        .collect();
+    if created.is_empty() {
+        info!(target: "mongodb", "Indexes are up to date");
+    } else {
+        info!(target: "mongodb", "Created indexes: {}", created.join(", "));
+    }
+    // the other indexes are still usable without it
+    if let Err(e) = ensure_unique_external_ids(&collection, &existing).await {
+        error!(target: "mongodb", "{e:?}");
+    }
+    if catalog.unique_titles {
+        ensure_unique_titles(&collection, mongodb, &existing).await?;
+        if !existing.iter().any(|name| name == UNIQUE_TITLES_INDEX) {
+            info!(target: "mongodb", "Created index {UNIQUE_TITLES_INDEX}");
+        }
+    }
+    Ok(())
+}
+
+fn unknown_kind_response() -> HttpResponse {
+    let kinds: Vec<&str> = SEASON_KINDS.iter().map(|kind| kind.as_str()).collect();
+    KError::bad_request(&format!("Kind must be one of {}", kinds.join(", ")))
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SeasonsQuery {
+    kind: Option<String>,
+}
+
+pub async fn fetch_anime_seasons(path: Path<String>, query: Query<SeasonsQuery>,
+    app: Data<AppState>) -> impl Responder {
+    let anime_id = match to_oid(&path.into_inner()) {
+        Ok(anime_id) => anime_id,
+        Err(e) => return KError::bad_request(&e.to_string())
+    };
+    let kind = match query.kind.as_deref().map(SeasonKind::parse) {
+        Some(Some(kind)) => Some(kind),
+        Some(None) => return unknown_kind_response(),
+        None => None
+    };
+    match find_anime(&anime_id, &app).await {
+        Ok(Some(anime)) => {
+            let seasons: Vec<&SeasonMapping> = anime.as_ref().mapping.iter()
+                .filter(|m| kind.is_none() || kind == Some(m.kind()))
+                .collect();
+            HttpResponse::Ok()
+                .insert_header(cache_control(&app, CacheKind::Details))
+                .json(seasons)
+        },
+        Ok(None) => KError::not_found(),
+        Err(e) => {
+            error!("Could not find anime: {e:?}");
+            KError::db_error()
+        }
+    }
+}
+
+pub async fn fetch_anime_by_slug(path: Path<String>, app: Data<AppState>) -> impl Responder {
+    let collection: mongodb::Collection<WithOID<AnimeSeries>> =
+        app.mongodb.database(DB_NAME).collection(COLL_NAME);
+    match collection.find_one(doc! { "slug": path.into_inner() }, None).await {
+        Ok(Some(anime)) => {
+            let renamed: WithID<AnimeSeries> = anime.into();
+            HttpResponse::Ok()
+                .insert_header(cache_control(&app, CacheKind::Details))
+                .json(renamed)
+        },
+        Ok(None) => KError::not_found(),
+        Err(e) => {
+            error!("Could not find anime by slug: {e:?}");
+            KError::db_error()
+        }
+    }
+}
+
+pub async fn fetch_anime_by_external_id(path: Path<(String, String)>, app: Data<AppState>) -> impl Responder {
+    let (source, id) = path.into_inner();
+    if !EXTERNAL_ID_SOURCES.contains(&source.as_str()) {
+        return KError::bad_request("Unknown external id source, expected `mal` or `anilist`");
+    }
+    let Ok(id) = id.parse::<u32>() else {
+        return KError::bad_request("The provided external ID is not valid");
+    };
+    let collection: mongodb::Collection<WithOID<AnimeSeries>> =
+        app.mongodb.database(DB_NAME).collection(COLL_NAME);
+    match collection.find_one(doc! { format!("externalIds.{source}"): id as i64 }, None).await {
+        Ok(Some(anime)) => {
+            let renamed: WithID<AnimeSeries> = anime.into();
+            HttpResponse::Ok()
+                .insert_header(cache_control(&app, CacheKind::Details))
+                .json(renamed)
+        },
+        Ok(None) => KError::not_found(),
+        Err(e) => {
+            error!("Could not find anime by external id: {e:?}");
+            KError::db_error()
+        }
+    }
+}
+
+/// Finds an anime other than `except` that already claims the external id
+async fn find_external_id_owner(source: &str, id: u32, except: Option<&ObjectId>, app: &AppState)
+    -> Result<Option<String>> {
+    let collection: mongodb::Collection<bson::Document> =
+        app.mongodb.database(DB_NAME).collection(COLL_NAME);
+    let mut filter = doc! { format!("externalIds.{source}"): id as i64 };
+    if let Some(except) = except {
+        filter.insert("_id", doc! { "$ne": except });
+    }
+    let owner = collection
+        .find_one(filter,
+            FindOneOptions::builder().projection(doc! { "_id": 1 }).build())
+        .await
+        .context("Finding the anime claiming the external id")?;
+    Ok(owner.and_then(|owner| owner.get_object_id("_id").ok()).map(|id| id.to_hex()))
+}
+
+async fn set_external_ids(anime_id: &ObjectId, external_ids: &ExternalIds, app: &AppState)
+    -> mongodb::error::Result<bool> {
+    let collection: mongodb::Collection<AnimeSeries> =
+        app.mongodb.database(DB_NAME).collection(COLL_NAME);
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .expect("The time can never be earlier than the Unix epoch")
+        .as_millis() as u64;
+    let mut update = doc! { "updatedOn": now as i64 };
+    for (source, id) in external_ids.entries() {
+        if let Some(id) = id {
+            update.insert(format!("externalIds.{source}"), id as i64);
+        }
+    }
+    let res = collection
+        .update_one(doc! { "_id": anime_id }, doc! { "$set": update }, None)
+        .await?;
+    Ok(res.matched_count > 0)
+}
+
+/// Attaches the given external ids to the anime, the others are left as they are
+async fn update_external_ids(params: Path<String>, body: Json<ExternalIds>, app: Data<AppState>) -> HttpResponse {
+    let anime_id = match to_oid(&params.into_inner()) {
+        Ok(anime_id) => anime_id,
+        Err(e) => return KError::bad_request(&e.to_string())
+    };
+    let external_ids = body.into_inner();
+    if external_ids.is_empty() {
+        return KError::bad_request("At least one of `mal` or `anilist` is required");
+    }
+    // the unique indexes reject the ids another anime has, checking beforehand would race
+    match set_external_ids(&anime_id, &external_ids, &app).await {
+        Ok(true) => {
+            info!("Updated external ids of `{}`", anime_id.to_hex());
+            HttpResponse::NoContent().finish()
+        },
+        Ok(false) => KError::not_found(),
+        Err(e) => match duplicate_external_id(&e) {
+            Some(source) => external_id_conflict(source, Some(&external_ids), Some(&anime_id), &app).await,
+            None => {
+                error!("Could not update external ids: {e:?}");
+                KError::db_error()
+            }
+        }
+    }
+}
+
+async fn send_anime_to_meili(anime: AnimeSeriesSearchEntry, app: &AppState) -> Result<()> {
+    app.meilisearch.get_index(ANIMES_INDEX)
+        .await?
+        .add_or_replace(&[anime], Some(ANIME_PRIMARY_KEY))
+        .await?
+        .wait_for_completion(&app.meilisearch, None, None)
+        .await?;
+    Ok(())
+}
+
+fn multipart_field_error(field_name: &str, source: &actix_web::Error) -> HttpResponse {
+    use actix_easy_multipart::{json::JsonFieldError, tempfile::TempfileError, text::TextError};
+    if let Some(e) = source.as_error::<JsonFieldError>() {
+        return match e {
+            JsonFieldError::Deserialize(e) => KError::bad_request(&format!("Invalid `{field_name}` JSON: {e}")),
+            _ => KError::bad_request(&format!("Field `{field_name}` must be sent as application/json")),
+        };
+    }
+    if let Some(e) = source.as_error::<TextError>() {
+        return KError::bad_request(&format!("Invalid `{field_name}` value: {e}"));
+    }
+    if let Some(TempfileError::FileIo(e)) = source.as_error::<TempfileError>() {
+        error!("Could not store uploaded field `{field_name}`: {e:?}");
+        return KError::internal_error("Could not store the uploaded file");
+    }
+    KError::bad_request(&format!("Invalid field `{field_name}`: {source}"))
+}
+
+fn multipart_error_handler(err: actix_easy_multipart::Error, _req: &HttpRequest) -> actix_web::Error {
+    use actix_easy_multipart::Error;
+    let res = match &err {
+        Error::Multipart(MultipartError::Payload(PayloadError::Overflow)) =>
+            KError::payload_too_large("The uploaded form is too large"),
+        Error::Multipart(e) => KError::bad_request(&format!("Invalid multipart body: {e}")),
+        Error::Field { field_name, source } => multipart_field_error(field_name, source),
+        Error::MissingField(field_name) => KError::bad_request(&format!("Missing field `{field_name}`")),
+        Error::DuplicateField(field_name) => KError::bad_request(&format!("Duplicate field `{field_name}`")),
+        Error::UnsupportedField(field_name) => KError::bad_request(&format!("Unsupported field `{field_name}`")),
+    };
+    InternalError::from_response(err, res).into()
+}
+
+pub fn multipart_form_config(max_poster_bytes: usize) -> MultipartFormConfig {
+    MultipartFormConfig::default()
+        .total_limit(max_poster_bytes + MULTIPART_MEMORY_LIMIT)
+        .memory_limit(MULTIPART_MEMORY_LIMIT)
+        .error_handler(multipart_error_handler)
+}
+
+fn unsupported_poster_response() -> HttpResponse {
+    let types: Vec<&str> = SUPPORTED_POSTER_TYPES.iter().map(|(content_type, _)| *content_type).collect();
+    KError::unsupported_media_type(&format!("Supported poster types are {}", types.join(", ")))
+}
+
+#[derive(MultipartForm)]
+struct AnimeMultipartCandidate {
+    // parsed by the handler, unknown fields are only rejected in strict mode
+    candidate: actix_easy_multipart::json::Json<serde_json::Value>,
+    poster: Tempfile,
+    force: Option<Text<bool>>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Identifier {
+    #[serde(rename = "_id")]
+    #[serde(with = "bson::serde_helpers::hex_string_as_object_id")]
+    id: String,
+}
+
+/// Looks for another anime than `except` with a title matching one of the given ones, ignoring
+/// case and spacing. Legacy documents without `normalizedTitles` are still matched
+/// case-insensitively.
+async fn find_duplicate(titles: &[Title], except: Option<&ObjectId>, app: &AppState) -> Result<Option<String>> {
+    let collection: mongodb::Collection<Identifier> =
+        app.mongodb.database(DB_NAME).collection(COLL_NAME);
+    let values = title_values(titles);
+    let mut filter = doc! {
+        "$or": [
+            { "normalizedTitles": { "$in": normalize_titles(titles) } },
+            { "titles.value": { "$in": &values } },
+            { "titles": { "$in": &values } }
+        ]
+    };
+    if let Some(except) = except {
+        filter.insert("_id", doc! { "$ne": except });
+    }
+    let options = FindOneOptions::builder()
+        .projection(doc! { "_id": 1 })
+        .collation(case_insensitive_collation())
+        .build();
+    let found = collection.find_one(filter, options).await
+        .context("Finding anime with a duplicate title")?;
+    Ok(found.map(|f| f.id))
+}
+
+async fn push_anime(form: MultipartForm<AnimeMultipartCandidate>, app: Data<AppState>) -> HttpResponse {
+    let form = form.into_inner();
+    let poster = form.poster;
+    let strict = app.catalog.strict_candidates(app.debug);
+    let candidate = match AnimeSeriesCandidate::from_json(form.candidate.into_inner(), strict) {
+        Ok(candidate) => candidate,
+        Err(e) => {
+            poster.file.close().unwrap_or_else(|_| warn!("Could not delete temp file"));
+            return KError::bad_request(&format!("Invalid `candidate` JSON: {e}"))
+        }
+    };
+    if let Err(e) = candidate.validate(&app.catalog) {
+        poster.file.close().unwrap_or_else(|_| warn!("Could not delete temp file"));
+        return KError::bad_request(&e)
+    }
+    let mut anime = {
+        let key: String = random_string::generate(20, CACHE_KEY_ALPHABET);
+        candidate.into_anime(CachedImage::new(key))
+    };
+
+    if poster.size > app.images.max_poster_bytes() {
+        poster.file.close().unwrap_or_else(|_| warn!("Could not delete temp file"));
+        return KError::payload_too_large("The uploaded poster is too large")
+    }
+
+    if !form.force.map(Text::into_inner).unwrap_or(false) {
+        match find_duplicate(&anime.titles, None, &app).await {
+            Ok(Some(id)) => {
+                poster.file.close().unwrap_or_else(|_| warn!("Could not delete temp file"));
+                return KError::conflict_with("An anime with the same title already exists", &id)
+            },
+            Ok(None) => {},
+            Err(e) => {
+                error!("Could not check for duplicates: {e:?}");
+                poster.file.close().unwrap_or_else(|_| warn!("Could not delete temp file"));
+                return KError::db_error()
+            }
+        }
+    }
+
+    let Some(format) = poster.content_type.as_ref().map(AsRef::as_ref).and_then(poster_format) else {
+        poster.file.close().unwrap_or_else(|_| warn!("Could not delete temp file"));
+        return unsupported_poster_response()
+    };
+    let from = poster.file.path().to_path_buf();
+    let cache_folder = app.cache_folder.clone();
+    let images = app.images.clone();
+    let exported = app.image_jobs.run(move || {
+        let key = anime.poster.key().to_string();
+        anime.poster = export_poster(key, &from, format, &cache_folder, &images)?;
+        if images.presenter_enabled() {
+            export_presenter(&anime, &cache_folder, images.resize_algorithm.into(),
+                images.fallback_poster.as_deref())
+                .unwrap_or_else(|_| warn!("Could not generate presenter"));
+        }
+        Ok(anime)
+    }).await;
+    poster.file.close().unwrap_or_else(|_| warn!("Could not delete temp file"));
+    let anime = match exported {
+        Ok(anime) => anime,
+        Err(e) if e.is::<AnimatedImageError>() => return KError::bad_request("Animated images are not supported"),
+        Err(e) => {
+            error!("Could not export poster: {e:?}");
+            return KError::internal_error("Could not generate image set")
+        }
+    };
+
+    insert_anime(anime, &app).await
+}
+
+#[derive(Deserialize, Debug)]
+struct PushQuery {
+    #[serde(default)]
+    force: bool,
+}
+
+/// Creates an anime from its metadata alone, the poster can be uploaded later through a patch
+async fn push_anime_json(candidate: Json<serde_json::Value>, query: Query<PushQuery>,
+    app: Data<AppState>) -> HttpResponse {
+    let strict = app.catalog.strict_candidates(app.debug);
+    let candidate = match AnimeSeriesCandidate::from_json(candidate.into_inner(), strict) {
+        Ok(candidate) => candidate,
+        Err(e) => return KError::bad_request(&format!("Invalid JSON body: {e}"))
+    };
+    if let Err(e) = candidate.validate(&app.catalog) {
+        return KError::bad_request(&e)
+    }
+    let key: String = random_string::generate(20, CACHE_KEY_ALPHABET);
+    let anime = candidate.into_anime(CachedImage::new(key));
+
+    if !query.force {
+        match find_duplicate(&anime.titles, None, &app).await {
+            Ok(Some(id)) => return KError::conflict_with("An anime with the same title already exists", &id),
+            Ok(None) => {},
+            Err(e) => {
+                error!("Could not check for duplicates: {e:?}");
+                return KError::db_error()
+            }
+        }
+    }
+    insert_anime(anime, &app).await
+}
+
+/// Picks `base` or the first `base-N` suffix, starting at 2, that no other anime uses
+async fn find_free_slug(base: &str, collection: &mongodb::Collection<AnimeSeries>) -> Result<String> {
+    let slugs: mongodb::Collection<AnimeSlug> = collection.clone_with_type();
+    let pattern = format!("^{}(-[0-9]+)?$", escape_regex(base));
+    let taken: BTreeSet<String> = slugs
+        .find(doc! { "slug": { "$regex": pattern } },
+            FindOptions::builder().projection(doc! { "_id": 0, "slug": 1 }).build())
+        .await.context("Finding similar slugs")?
+        .map_ok(|s| s.slug)
+        .try_collect().await
+        .context("Collecting similar slugs")?;
+    if !taken.contains(base) {
+        return Ok(base.to_string());
+    }
+    let free = (2..).map(|n| format!("{base}-{n}"))
+        .find(|slug| !taken.contains(slug))
+        .expect("There are fewer taken slugs than integers");
+    Ok(free)
+}
+
+#[derive(Deserialize, Debug)]
+struct AnimeSlug {
+    slug: String,
+}
+
+fn is_duplicate_key(e: &mongodb::error::Error) -> bool {
+    use mongodb::error::{ErrorKind, WriteFailure};
+    matches!(&*e.kind, ErrorKind::Write(WriteFailure::WriteError(e)) if e.code == MONGODB_DUPLICATE_KEY)
+}
+
+/// Inserts the anime with a slug no other anime uses, it is picked again if another anime took it
+/// in the meantime
+async fn insert_with_slug(anime: &mut AnimeSeries, collection: &mongodb::Collection<AnimeSeries>)
+    -> Result<InsertOneResult> {
+    let base = slugify(anime.primary_title().unwrap_or_default());
+    let mut attempt = 1;
+    loop {
+        anime.slug = find_free_slug(&base, collection).await?;
+        match collection.insert_one(&*anime, None).await {
+            Err(e) if is_duplicate_key(&e) && !is_duplicate_title(&e) && duplicate_external_id(&e).is_none()
+                && attempt < ANIME_SLUG_INSERT_ATTEMPTS => attempt += 1,
+            res => return res.context("Inserting anime"),
+        }
+    }
+}
+
+async fn insert_anime(mut anime: AnimeSeries, app: &AppState) -> HttpResponse {
+    let collection: mongodb::Collection<AnimeSeries> =
+        app.mongodb.database(DB_NAME).collection(COLL_NAME);
+    match insert_with_slug(&mut anime, &collection).await {
+        Ok(InsertOneResult { inserted_id, .. }) => {
+            let inserted_id = inserted_id.as_object_id()
+                .expect("Value must be ObjectId").to_hex();
+            let anime = WithID::new(inserted_id, anime);
+            if let Err(e) = send_anime_to_meili(anime.clone().into(), app).await {
+                warn!("Could not add pushed anime to meilisearch: {e:?}");
+            }
+            // TODO: Maybe try to not rebuild everything but just add the new anime
+            if let Err(e) = seo::build_sitemap(app).await {
+                warn!("Could not rebuild sitemap: {e:?}");
+            }
+            HttpResponse::Created()
+                .insert_header((header::LOCATION, format!("/anime/{}", anime.id)))
+                .json(anime)
+        },
+        Err(e) => {
+            delete_cached_images(anime.poster.key(), &app.cache_folder);
+            if matches!(e.downcast_ref(), Some(e) if is_duplicate_title(e)) {
+                return KError::conflict("An anime with the same title already exists")
+            }
+            if let Some(source) = e.downcast_ref().and_then(duplicate_external_id) {
+                return external_id_conflict(source, Some(&anime.external_ids), None, app).await;
+            }
+            error!("Could not push anime to db: {e:?}");
             KError::db_error()
         }
     }
@@ -257,7 +1837,7 @@ async fn apply_anime_patch(anime_id: &ObjectId, app: &AppState, mut patch: Anime
     let collection: mongodb::Collection<AnimeSeries> =
         app.mongodb.database(DB_NAME).collection(COLL_NAME);
     let res = collection
-        .update_one(doc! { "_id": anime_id }, doc! { "$set": patch.seal()? }, None)
+        .update_one(doc! { "_id": anime_id }, patch.seal()?, None)
         .await
         .context("Updating anime with the specified ID")?;
     if res.matched_count == 0 {
@@ -274,72 +1854,252 @@ async fn apply_anime_patch(anime_id: &ObjectId, app: &AppState, mut patch: Anime
     Ok(true)
 }
 
+/// Answers the patch when the unique indexes would reject it, checked before any image is
+/// rendered with the patched values
+async fn patch_conflict(anime_id: &ObjectId, patch: &AnimeSeriesPatch, app: &AppState) -> Option<HttpResponse> {
+    if let Some(titles) = patch.titles().filter(|_| app.catalog.unique_titles) {
+        match find_duplicate(titles, Some(anime_id), app).await {
+            Ok(Some(id)) => return Some(KError::conflict_with("An anime with the same title already exists", &id)),
+            Ok(None) => {},
+            Err(e) => {
+                error!("Could not check for duplicates: {e:?}");
+                return Some(KError::db_error())
+            }
+        }
+    }
+    for (source, id) in patch.external_ids().map(ExternalIds::entries).unwrap_or_default() {
+        let Some(id) = id else {
+            continue;
+        };
+        match find_external_id_owner(source, id, Some(anime_id), app).await {
+            Ok(Some(owner)) => return Some(KError::conflict_with(&external_id_conflict_details(source), &owner)),
+            Ok(None) => {},
+            Err(e) => {
+                error!("Could not check for claimed external ids: {e:?}");
+                return Some(KError::db_error())
+            }
+        }
+    }
+    None
+}
+
+/// Sent instead of an empty response when the patch was applied but a step around it failed
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct PatchReport {
+    applied: bool,
+    poster_updated: bool,
+    warnings: Vec<String>,
+}
+
 async fn patch_anime(params: Path<String>, form: MultipartForm<AnimeMultipartPatch>,
     app: Data<AppState>) -> HttpResponse {
-    let Some(anime_id) = to_oid(&params.into_inner()) else {
-        return KError::bad_request("The provided ID is not valid");
+    let anime_id = match to_oid(&params.into_inner()) {
+        Ok(anime_id) => anime_id,
+        Err(e) => return KError::bad_request(&e.to_string())
     };
     let form = form.into_inner();
     let mut patch = form.patch.into_inner();
     if patch.is_empty() && form.poster.is_none() {
         return KError::bad_request("Patch is empty")
     }
+    if let Err(e) = patch.validate(&app.catalog) {
+        if let Some(poster) = form.poster {
+            poster.file.close().unwrap_or_else(|_| warn!("Could not delete temp file"));
+        }
+        return KError::bad_request(&e)
+    }
+    if let Some(res) = patch_conflict(&anime_id, &patch, &app).await {
+        if let Some(poster) = form.poster {
+            poster.file.close().unwrap_or_else(|_| warn!("Could not delete temp file"));
+        }
+        return res
+    }
 
+    let mut warnings = vec![];
+    let mut poster_updated = false;
     if let Some(poster) = form.poster {
-        match poster.content_type.as_ref().map(AsRef::as_ref) {
-            Some("image/webp"/* | "image/png"*/) => {
+        if poster.size > app.images.max_poster_bytes() {
+            poster.file.close().unwrap_or_else(|_| warn!("Could not delete temp file"));
+            return KError::payload_too_large("The uploaded poster is too large")
+        }
+        match poster.content_type.as_ref().map(AsRef::as_ref).and_then(poster_format) {
+            Some(format) => {
                 let Ok(Some(anime)) = find_anime(&anime_id, &app).await else {
                     poster.file.close().unwrap_or_else(|_| warn!("Could not delete temp file"));
                     return KError::bad_request("The provided ID is not valid");
                 };
                 let mut anime = anime.into_inner();
-                let key = anime.poster.key().to_string();
-                match export_poster(key, poster.file.path(), &app.cache_folder) {
+                // the presenter is rendered with the patched values and the new poster
+                patch.clone().apply(&mut anime);
+                let from = poster.file.path().to_path_buf();
+                let cache_folder = app.cache_folder.clone();
+                let images = app.images.clone();
+                let exported = app.image_jobs.run(move || {
+                    let key = anime.poster.key().to_string();
+                    anime.poster = export_poster(key, &from, format, &cache_folder, &images)?;
+                    if images.presenter_enabled() {
+                        export_presenter(&anime, &cache_folder, images.resize_algorithm.into(),
+                            images.fallback_poster.as_deref())
+                            .unwrap_or_else(|_| warn!("Could not generate presenter"));
+                    }
+                    Ok(anime.poster)
+                }).await;
+                poster.file.close().unwrap_or_else(|_| warn!("Could not delete temp file"));
+                match exported {
                     Ok(ci) => {
                         patch.set_poster(ci);
-                        patch.clone().apply(&mut anime);
-                        export_presenter(&anime, &app.cache_folder)
-                            .unwrap_or_else(|_| warn!("Could not generate presenter"));
+                        poster_updated = true;
                     },
+                    Err(e) if e.is::<AnimatedImageError>() =>
+                        return KError::bad_request("Animated images are not supported"),
                     Err(e) => {
                         error!("Could not export poster: {e:?}");
                         if patch.is_empty() {
-                            poster.file.close().unwrap_or_else(|_| warn!("Could not delete temp file"));
                             return KError::internal_error("Could not generate image set")
                         }
+                        warnings.push("Could not generate image set, the poster was left unchanged".to_string());
                     }
                 }
-                poster.file.close().unwrap_or_else(|_| warn!("Could not delete temp file"));
             },
-            _ => {
+            None => {
                 poster.file.close().unwrap_or_else(|_| warn!("Could not delete temp file"));
-                return KError::bad_request("Only webp or png images are supported")
+                return unsupported_poster_response()
             }
         }
-    } else if patch.has_presenter_changes() {
+    } else if app.images.presenter_enabled() && patch.has_presenter_changes() {
         let Ok(Some(anime)) = find_anime(&anime_id, &app).await else {
             return KError::bad_request("The provided ID is not valid");
         };
         let mut anime = anime.into_inner();
-        patch.clone().apply(&mut anime);
-        match export_presenter(anime, &app.cache_folder) {
-            Ok(()) => info!("Successfully updated presenter for `{}`", anime_id.to_hex()),
-            Err(e) => warn!("Could not generate presenter image: {e:?}")
+        if patch.differs_on_presenter(&anime) {
+            patch.clone().apply(&mut anime);
+            let cache_folder = app.cache_folder.clone();
+            let algorithm = app.images.resize_algorithm.into();
+            let fallback = app.images.fallback_poster.clone();
+            match app.image_jobs.run(move || export_presenter(anime, &cache_folder, algorithm,
+                fallback.as_deref())).await {
+                Ok(()) => info!("Successfully updated presenter for `{}`", anime_id.to_hex()),
+                Err(e) => warn!("Could not generate presenter image: {e:?}")
+            }
+        } else {
+            info!("Presenter for `{}` is already up to date", anime_id.to_hex());
+        }
+    }
+
+    let external_ids = patch.external_ids().copied();
+    match apply_anime_patch(&anime_id, &app, patch).await {
+        Ok(true) if warnings.is_empty() => HttpResponse::NoContent().finish(),
+        Ok(true) => HttpResponse::Ok().json(PatchReport { applied: true, poster_updated, warnings }),
+        Ok(false) => KError::not_found(),
+        Err(e) if matches!(e.downcast_ref(), Some(e) if is_duplicate_title(e)) => KError::conflict("An anime with the same title already exists"),
+        Err(e) => match e.downcast_ref().and_then(duplicate_external_id) {
+            Some(source) => external_id_conflict(source, external_ids.as_ref(), Some(&anime_id), &app).await,
+            None => {
+                error!("Could not find anime:\n{e:?}");
+                KError::db_error()
+            }
         }
     }
+}
 
+/// Computes the placeholder of a poster exported before placeholders existed, from the stored
+/// full resolution image
+async fn compute_anime_placeholder(params: Path<String>, app: Data<AppState>) -> HttpResponse {
+    let anime_id = match to_oid(&params.into_inner()) {
+        Ok(anime_id) => anime_id,
+        Err(e) => return KError::bad_request(&e.to_string())
+    };
+    let anime = match find_anime(&anime_id, &app).await {
+        Ok(Some(anime)) => anime.into_inner(),
+        Ok(None) => return KError::not_found(),
+        Err(e) => {
+            error!("Could not find anime: {e:?}");
+            return KError::db_error()
+        }
+    };
+    let key = anime.poster.key().to_string();
+    let cache_folder = app.cache_folder.clone();
+    let images = app.images.clone();
+    let poster = match app.image_jobs.run(move || recompute_placeholder(key, &cache_folder, &images)).await {
+        Ok(Some(poster)) => poster,
+        Ok(None) => return KError::not_found(),
+        Err(e) => {
+            error!("Could not compute placeholder: {e:?}");
+            return KError::internal_error("Could not compute placeholder")
+        }
+    };
+    let mut patch = AnimeSeriesPatch::default();
+    patch.set_poster(poster.clone());
     match apply_anime_patch(&anime_id, &app, patch).await {
+        Ok(true) => {
+            info!("Computed placeholder for `{}`", anime_id.to_hex());
+            HttpResponse::Ok().json(poster)
+        },
+        Ok(false) => KError::not_found(),
+        Err(e) => {
+            error!("Could not save placeholder: {e:?}");
+            KError::db_error()
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct PinnedNoteUpdate {
+    content: Option<String>,
+}
+
+async fn set_pinned_note(anime_id: &ObjectId, index: usize, note: Option<Note>, app: &AppState)
+    -> Result<bool> {
+    let collection: mongodb::Collection<AnimeSeries> =
+        app.mongodb.database(DB_NAME).collection(COLL_NAME);
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .expect("The time can never be earlier than the Unix epoch")
+        .as_millis() as u64;
+    let res = collection
+        .update_one(
+            doc! { "_id": anime_id, format!("mapping.{index}"): { "$exists": true } },
+            doc! { "$set": {
+                format!("mapping.{index}.pinnedNote"): bson::to_bson(&note)?,
+                "updatedOn": now as i64
+            } },
+            None)
+        .await
+        .context("Updating pinned note of the specified mapping")?;
+    Ok(res.matched_count > 0)
+}
+
+async fn update_pinned_note(params: Path<(String, usize)>, update: Json<PinnedNoteUpdate>,
+    session: ReqData<Session>, app: Data<AppState>) -> HttpResponse {
+    let (anime_id, index) = params.into_inner();
+    let anime_id = match to_oid(&anime_id) {
+        Ok(anime_id) => anime_id,
+        Err(e) => return KError::bad_request(&e.to_string())
+    };
+    let note = match update.into_inner().content {
+        Some(content) if content.trim().is_empty() || content.chars().count() > NOTE_MAX_LEN =>
+            return KError::bad_request("Note content must be between 1 and 1024 characters"),
+        Some(content) => Some(Note::new(session.user_id.clone(), content)),
+        None => None
+    };
+    match set_pinned_note(&anime_id, index, note, &app).await {
         Ok(true) => HttpResponse::NoContent().finish(),
         Ok(false) => KError::not_found(),
         Err(e) => {
-            error!("Could not find anime:\n{e:?}");
+            error!("Could not update pinned note: {e:?}");
             KError::db_error()
         }
     }
 }
 
-fn create_backup(anime: &WithID<AnimeSeries>) -> anyhow::Result<()> {
-    let backup = File::create(format!("{}.deleted.json", anime.id))?;
+fn get_backup_path(anime_id: &str, cache_folder: &std::path::Path) -> std::path::PathBuf {
+    cache_folder.join(BACKUP_FOLDER).join(format!("{anime_id}{BACKUP_EXTENSION}"))
+}
+
+fn create_backup(anime: &WithID<AnimeSeries>, cache_folder: &std::path::Path) -> anyhow::Result<()> {
+    std::fs::create_dir_all(cache_folder.join(BACKUP_FOLDER))?;
+    let backup = File::create(get_backup_path(&anime.id, cache_folder))?;
     if let Err(e) = serde_json::to_writer(backup, &anime) {
         let json = serde_json::to_string(&anime)?;
         warn!("Could not save backup file ({e:?}), anime = `{json}`");
@@ -364,14 +2124,18 @@ async fn delete_from_meili(anime_id: &str, app: &AppState) -> Result<()> {
 }
 
 async fn delete_anime(path: Path<String>, app: Data<AppState>) -> HttpResponse {
-    let Some(anime_id) = to_oid(&path.into_inner()) else {
-        return KError::bad_request("The provided ID is not valid");
+    let anime_id = match to_oid(&path.into_inner()) {
+        Ok(anime_id) => anime_id,
+        Err(e) => return KError::bad_request(&e.to_string())
     };
     match find_and_delete(&anime_id, &app).await {
         Ok(Some(anime)) => {
             let anime: WithID<AnimeSeries> = anime.into();
-            create_backup(&anime)
+            create_backup(&anime, &app.cache_folder)
                 .unwrap_or_else(|e| error!("Could not save backup file `{anime:?}`: {e:?}"));
+            // kept next to the backup so that the anime can be restored with its images
+            backup_cached_images(anime.as_ref().poster.key(), &app.cache_folder,
+                &app.cache_folder.join(BACKUP_FOLDER));
 
             if let Err(e) = delete_from_meili(&anime.id, &app).await {
                 warn!("Could not remove deleted anime from meilisearch: {e:?}");
@@ -392,21 +2156,789 @@ async fn delete_anime(path: Path<String>, app: Data<AppState>) -> HttpResponse {
     }
 }
 
-pub fn configure(cfg: &mut web::ServiceConfig) {
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+enum BulkDeleteStatus {
+    Deleted,
+    NotFound,
+}
+
+#[derive(Serialize, Debug, Clone)]
+struct BulkDeleteResult {
+    id: String,
+    status: BulkDeleteStatus,
+}
+
+async fn find_many(anime_ids: &[ObjectId], app: &AppState) -> Result<Vec<WithOID<AnimeSeries>>> {
+    let collection: mongodb::Collection<WithOID<AnimeSeries>> =
+        app.mongodb.database(DB_NAME).collection(COLL_NAME);
+    collection.find(doc! { "_id": { "$in": anime_ids } }, None).await
+        .context("Finding animes with the specified IDs")?
+        .try_collect().await
+        .context("Collecting animes with the specified IDs")
+}
+
+async fn delete_many(anime_ids: &[ObjectId], app: &AppState) -> Result<()> {
+    let collection: mongodb::Collection<AnimeSeries> =
+        app.mongodb.database(DB_NAME).collection(COLL_NAME);
+    collection.delete_many(doc! { "_id": { "$in": anime_ids } }, None).await
+        .context("Deleting animes with the specified IDs")?;
+    Ok(())
+}
+
+async fn bulk_delete_animes(ids: Json<Vec<String>>, app: Data<AppState>) -> HttpResponse {
+    if ids.is_empty() || ids.len() > BULK_DELETE_MAX_IDS {
+        return KError::bad_request("Between 1 and 100 IDs can be deleted at once");
+    }
+    let anime_ids = match ids.iter().map(|id| to_oid(id).map_err(|e| (id, e))).collect::<Result<Vec<_>, _>>() {
+        Ok(anime_ids) => anime_ids,
+        Err((id, e)) => return KError::bad_request(&format!("Invalid ID `{id}`: {e}"))
+    };
+    let found: Vec<WithID<AnimeSeries>> = match find_many(&anime_ids, &app).await {
+        Ok(found) => found.into_iter().map(Into::into).collect(),
+        Err(e) => {
+            error!("Could not find animes: {e:?}");
+            return KError::db_error();
+        }
+    };
+    for anime in &found {
+        create_backup(anime, &app.cache_folder)
+            .unwrap_or_else(|e| error!("Could not save backup file `{anime:?}`: {e:?}"));
+    }
+    let found_ids: Vec<ObjectId> = found.iter().filter_map(|anime| to_oid(&anime.id).ok()).collect();
+    if let Err(e) = delete_many(&found_ids, &app).await {
+        error!("Could not delete animes: {e:?}");
+        return KError::db_error();
+    }
+
+    for anime in &found {
+        backup_cached_images(anime.as_ref().poster.key(), &app.cache_folder, &app.cache_folder.join(BACKUP_FOLDER));
+        if let Err(e) = delete_from_meili(&anime.id, &app).await {
+            warn!("Could not remove deleted anime from meilisearch: {e:?}");
+        }
+    }
+    if !found.is_empty() {
+        if let Err(e) = seo::build_sitemap(&app).await {
+            warn!("Could not rebuild sitemap: {e:?}");
+        }
+    }
+
+    let results: Vec<BulkDeleteResult> = anime_ids.iter()
+        .map(|oid| {
+            let id = oid.to_hex();
+            let status = if found.iter().any(|anime| anime.id == id) {
+                BulkDeleteStatus::Deleted
+            } else {
+                BulkDeleteStatus::NotFound
+            };
+            BulkDeleteResult { id, status }
+        })
+        .collect();
+    HttpResponse::Ok().json(results)
+}
+
+async fn regenerate_all_presenters(app: Data<AppState>, progress: JobProgress) -> Result<()> {
+    let collection: mongodb::Collection<AnimeSeries> =
+        app.mongodb.database(DB_NAME).collection(COLL_NAME);
+    progress.set_total(collection.count_documents(None, None).await?).await;
+    let cursor = collection
+        .find(doc! {}, FindOptions::builder()
+            .batch_size(ANIMES_INDEX_BATCH_SIZE as u32).build())
+        .await?;
+    let mut renders = cursor
+        .map(|anime| {
+            let cache_folder = app.cache_folder.clone();
+            let algorithm = app.images.resize_algorithm.into();
+            let fallback = app.images.fallback_poster.clone();
+            let image_jobs = app.image_jobs.clone();
+            async move {
+                let anime = anime?;
+                image_jobs.run(move || export_presenter(&anime, &cache_folder, algorithm,
+                    fallback.as_deref())).await
+            }
+        })
+        .buffer_unordered(PRESENTER_REGENERATION_CONCURRENCY);
+    let mut failed = 0;
+    while let Some(res) = renders.next().await {
+        if let Err(e) = res {
+            warn!("Could not regenerate presenter: {e:?}");
+            failed += 1;
+        }
+        progress.advance().await;
+    }
+    if failed > 0 {
+        warn!("{failed} presenters could not be regenerated");
+    }
+    Ok(())
+}
+
+async fn regenerate_presenters(app: Data<AppState>) -> HttpResponse {
+    if !app.images.presenter_enabled() {
+        return KError::service_unavailable("Presenters are disabled on this instance")
+    }
+    let job_app = app.clone();
+    let job = spawn_job(&app.redis, PRESENTER_REGENERATION_JOB,
+        move |progress| regenerate_all_presenters(job_app, progress)).await;
+    match job {
+        Ok(job) => job_accepted(&job),
+        Err(e) => {
+            error!("Could not start presenter regeneration: {e:?}");
+            KError::internal_error("Could not start presenter regeneration")
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct BackupEntry {
+    id: String,
+    /// Unix timestamp in milliseconds of when the backup was written
+    deleted_on: u64,
+}
+
+fn list_backups(cache_folder: &std::path::Path) -> std::io::Result<Vec<BackupEntry>> {
+    let entries = match std::fs::read_dir(cache_folder.join(BACKUP_FOLDER)) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+    let mut backups = vec![];
+    for entry in entries {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let Some(id) = file_name.to_str().and_then(|name| name.strip_suffix(BACKUP_EXTENSION)) else {
+            continue;
+        };
+        if to_oid(id).is_err() {
+            continue;
+        }
+        let deleted_on = entry.metadata()?.modified()?
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        backups.push(BackupEntry { id: id.to_string(), deleted_on });
+    }
+    // most recent deletions first
+    backups.sort_by(|a, b| b.deleted_on.cmp(&a.deleted_on).then_with(|| a.id.cmp(&b.id)));
+    Ok(backups)
+}
+
+async fn fetch_deleted_animes(page: Query<PageQuery>, app: Data<AppState>) -> HttpResponse {
+    let cache_folder = app.cache_folder.clone();
+    let backups = match web::block(move || list_backups(&cache_folder)).await {
+        Ok(Ok(backups)) => backups,
+        Ok(Err(e)) => {
+            error!("Could not list backups: {e:?}");
+            return KError::internal_error("Could not list backups");
+        },
+        Err(e) => {
+            error!("Could not list backups: {e:?}");
+            return KError::internal_error("Could not list backups");
+        }
+    };
+    let limit = page.limit.unwrap_or(ANIMES_LIST_DEFAULT_LIMIT)
+        .min(ANIMES_LIST_SOFT_LIMIT) as usize;
+    let offset = page.offset.unwrap_or(0) as usize;
+    let page: Vec<BackupEntry> = backups.into_iter().skip(offset).take(limit).collect();
+    HttpResponse::Ok().json(page)
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ExportQuery {
+    #[serde(default)]
+    gzip: bool,
+}
+
+struct ExportStream {
+    cursor: mongodb::Cursor<WithOID<AnimeSeries>>,
+    encoder: Option<flate2::write::GzEncoder<Vec<u8>>>,
+    exported: usize,
+}
+
+/// Streams one anime per line in chunks, compressing them as they go when an encoder is given.
+/// A document that cannot be read aborts the stream, an export must not silently miss animes.
+fn stream_export(state: ExportStream) -> impl Stream<Item = Result<Bytes, actix_web::Error>> {
+    use std::io::Write;
+    futures::stream::unfold(Some(state), |state| async move {
+        let mut state = state?;
+        let mut chunk = Vec::new();
+        let mut done = false;
+        for _ in 0..ANIMES_EXPORT_CHUNK_SIZE {
+            match state.cursor.try_next().await {
+                Ok(Some(anime)) => {
+                    let anime: WithID<AnimeSeries> = anime.into();
+                    serde_json::to_writer(&mut chunk, &anime).expect("Animes should always be serializable");
+                    chunk.push(b'\n');
+                    state.exported += 1;
+                },
+                Ok(None) => {
+                    done = true;
+                    break;
+                },
+                Err(e) => {
+                    error!("Could not export animes after {} of them: {e:?}", state.exported);
+                    return Some((Err(actix_web::error::ErrorInternalServerError("Could not export animes")), None));
+                }
+            }
+        }
+        if let Some(encoder) = &mut state.encoder {
+            // writing to memory cannot fail
+            encoder.write_all(&chunk).expect("Could not compress export");
+            chunk = if done {
+                let encoder = state.encoder.take().expect("The encoder was just used");
+                encoder.finish().expect("Could not compress export")
+            } else {
+                std::mem::take(encoder.get_mut())
+            };
+        }
+        if !done {
+            return Some((Ok(Bytes::from(chunk)), Some(state)));
+        }
+        info!("Exported {} animes", state.exported);
+        Some((Ok(Bytes::from(chunk)), None))
+    })
+}
+
+/// Dumps the whole collection as NDJSON without holding it in memory, in `_id` order
+async fn export_animes(query: Query<ExportQuery>, app: Data<AppState>) -> HttpResponse {
+    let collection: mongodb::Collection<WithOID<AnimeSeries>> =
+        app.mongodb.database(DB_NAME).collection(COLL_NAME);
+    let options = FindOptions::builder()
+        .sort(doc! { "_id": 1 })
+        .build();
+    let cursor = match collection.find(None, options).await {
+        Ok(cursor) => cursor,
+        Err(e) => {
+            error!("Could not export animes: {e:?}");
+            return KError::db_error();
+        }
+    };
+    let encoder = query.gzip
+        .then(|| flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default()));
+    let mut res = HttpResponse::Ok();
+    res.content_type(NDJSON_CONTENT_TYPE);
+    if encoder.is_some() {
+        res.insert_header((header::CONTENT_ENCODING, "gzip"));
+    }
+    res.streaming(stream_export(ExportStream { cursor, encoder, exported: 0 }))
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ImportError {
+    /// Starting at 1
+    line: usize,
+    reason: String,
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+struct ImportReport {
+    inserted: usize,
+    updated: usize,
+    failed: usize,
+    /// Imported animes sent to the index, the others are picked up by the next sync
+    indexed: usize,
+    /// The first failures only
+    errors: Vec<ImportError>,
+    /// Why the rest of the body was not imported, what comes before is applied
+    #[serde(skip_serializing_if = "Option::is_none")]
+    aborted: Option<String>,
+}
+
+impl ImportReport {
+    fn fail(&mut self, line: usize, reason: String) {
+        self.failed += 1;
+        if self.errors.len() < ANIMES_IMPORT_MAX_REPORTED_ERRORS {
+            self.errors.push(ImportError { line, reason });
+        }
+    }
+}
+
+/// Reads one line of the import, the normalized titles are computed again as the unique titles
+/// index relies on them
+fn parse_import_line(line: &[u8]) -> Result<(ObjectId, WithOID<AnimeSeries>), String> {
+    let anime: WithID<AnimeSeries> = serde_json::from_slice(line).map_err(|e| e.to_string())?;
+    let oid = to_oid(&anime.id).map_err(|e| format!("Invalid ID `{}`: {e}", anime.id))?;
+    let id = anime.id.clone();
+    let mut anime = anime.into_inner();
+    anime.normalized_titles = normalize_titles(&anime.titles);
+    Ok((oid, WithID::new(id, anime).into()))
+}
+
+/// Upserts the batch one document at a time so that a failure only loses that anime, then pushes
+/// what was written to the index
+async fn import_batch(batch: &mut Vec<(usize, ObjectId, WithOID<AnimeSeries>)>, report: &mut ImportReport,
+                      app: &AppState) {
+    let collection: mongodb::Collection<WithOID<AnimeSeries>> =
+        app.mongodb.database(DB_NAME).collection(COLL_NAME);
+    let options = ReplaceOptions::builder().upsert(true).build();
+    let mut written = Vec::with_capacity(batch.len());
+    for (line, oid, anime) in batch.drain(..) {
+        match collection.replace_one(doc! { "_id": oid }, &anime, options.clone()).await {
+            Ok(res) if res.upserted_id.is_some() => report.inserted += 1,
+            Ok(_) => report.updated += 1,
+            Err(e) if is_duplicate_title(&e) => {
+                report.fail(line, "Another anime already has one of these titles".to_string());
+                continue;
+            },
+            Err(e) => {
+                let reason = match duplicate_external_id(&e) {
+                    Some(source) => format!("Another anime already has this {source} id"),
+                    None => {
+                        warn!("Could not import anime `{}`: {e:?}", anime.id);
+                        "Could not write anime".to_string()
+                    }
+                };
+                report.fail(line, reason);
+                continue;
+            }
+        }
+        written.push(oid);
+    }
+    if written.is_empty() {
+        return;
+    }
+    let index = app.meilisearch.index(ANIMES_INDEX);
+    match push_documents(&collection, &index, Some(doc! { "_id": { "$in": &written } })).await {
+        Ok(summary) => report.indexed += summary.sent,
+        Err(e) => warn!(target: "meilisearch", "Could not index imported animes: {e:?}")
+    }
+}
+
+/// Upserts the animes of an NDJSON body, as produced by the export and gzipped or not according
+/// to `Content-Encoding`, keeping their IDs. Unreadable lines are reported and skipped. When the
+/// body cannot be read to the end, the report of what was imported comes with a `400` or `413`.
+/// The path is exempted from the request timeout.
+async fn import_animes(req: HttpRequest, payload: web::Payload, app: Data<AppState>) -> HttpResponse {
+    let mut body = actix_web::dev::Decompress::from_headers(payload.into_inner(), req.headers());
+    let mut report = ImportReport::default();
+    let mut batch = Vec::with_capacity(ANIMES_IMPORT_BATCH_SIZE);
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut line_number = 0;
+    let mut ended = false;
+    while !ended {
+        match body.next().await {
+            Some(Ok(chunk)) => buffer.extend_from_slice(&chunk),
+            Some(Err(e)) => {
+                warn!("Could not read import body after {line_number} lines: {e}");
+                return aborted_import(report, batch, "Could not read body".to_string(),
+                    StatusCode::BAD_REQUEST, &app).await;
+            },
+            // the last line may lack its line feed
+            None => {
+                ended = true;
+                if !buffer.is_empty() {
+                    buffer.push(b'\n');
+                }
+            }
+        }
+        let mut consumed = 0;
+        while let Some(pos) = buffer[consumed..].iter().position(|b| *b == b'\n') {
+            let line = &buffer[consumed..consumed + pos];
+            consumed += pos + 1;
+            line_number += 1;
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            match parse_import_line(line) {
+                Ok((oid, anime)) => batch.push((line_number, oid, anime)),
+                Err(reason) => report.fail(line_number, reason)
+            }
+            if batch.len() == ANIMES_IMPORT_BATCH_SIZE {
+                import_batch(&mut batch, &mut report, &app).await;
+            }
+        }
+        buffer.drain(..consumed);
+        if buffer.len() > ANIMES_IMPORT_MAX_LINE_BYTES {
+            let reason = format!("Line {} is longer than {ANIMES_IMPORT_MAX_LINE_BYTES} bytes", line_number + 1);
+            return aborted_import(report, batch, reason, StatusCode::PAYLOAD_TOO_LARGE, &app).await;
+        }
+    }
+    import_batch(&mut batch, &mut report, &app).await;
+    info!("Imported {} new and {} existing animes, {} failed", report.inserted, report.updated, report.failed);
+    HttpResponse::Ok().json(report)
+}
+
+/// Still imports the complete lines read so far
+async fn aborted_import(mut report: ImportReport, mut batch: Vec<(usize, ObjectId, WithOID<AnimeSeries>)>,
+                        reason: String, status: StatusCode, app: &AppState) -> HttpResponse {
+    import_batch(&mut batch, &mut report, app).await;
+    warn!("Import aborted after {} new and {} existing animes: {reason}", report.inserted, report.updated);
+    report.aborted = Some(reason);
+    HttpResponse::build(status).json(report)
+}
+
+fn read_backup(path: &std::path::Path) -> Result<Option<WithID<AnimeSeries>>> {
+    let content = match std::fs::read(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).context("Reading backup file"),
+    };
+    Ok(Some(serde_json::from_slice(&content).context("Parsing backup file")?))
+}
+
+/// Keeps the slug the anime had if it is still free, picks a new one otherwise
+async fn restore_slug(anime: &mut AnimeSeries, collection: &mongodb::Collection<AnimeSeries>) -> Result<()> {
+    if !anime.slug.is_empty() {
+        let taken = collection.count_documents(doc! { "slug": &anime.slug }, None).await
+            .context("Checking if the slug is taken")?;
+        if taken == 0 {
+            return Ok(());
+        }
+    }
+    anime.slug = find_free_slug(&slugify(anime.primary_title().unwrap_or_default()), collection).await?;
+    Ok(())
+}
+
+/// Puts a deleted anime back with its original ID, along with the images moved next to its backup
+async fn undelete_anime(path: Path<String>, app: Data<AppState>) -> HttpResponse {
+    let anime_id = match to_oid(&path.into_inner()) {
+        Ok(anime_id) => anime_id,
+        Err(e) => return KError::bad_request(&e.to_string())
+    };
+    let backup_path = get_backup_path(&anime_id.to_hex(), &app.cache_folder);
+    let read_path = backup_path.clone();
+    let anime = match web::block(move || read_backup(&read_path)).await {
+        Ok(Ok(Some(anime))) if anime.id == anime_id.to_hex() => anime,
+        Ok(Ok(Some(_))) => return KError::internal_error("The backup does not match the provided ID"),
+        Ok(Ok(None)) => return KError::not_found(),
+        Ok(Err(e)) => {
+            error!("Could not read backup: {e:?}");
+            return KError::internal_error("Could not read backup");
+        },
+        Err(e) => {
+            error!("Could not read backup: {e:?}");
+            return KError::internal_error("Could not read backup");
+        }
+    };
+    match find_anime(&anime_id, &app).await {
+        Ok(Some(_)) => return KError::conflict_with("An anime with this ID already exists", &anime.id),
+        Ok(None) => {},
+        Err(e) => {
+            error!("Could not check for existing anime: {e:?}");
+            return KError::db_error();
+        }
+    }
+    // another anime may have taken one of the titles since the deletion
+    match find_duplicate(&anime.as_ref().titles, Some(&anime_id), &app).await {
+        Ok(Some(id)) => return KError::conflict_with("An anime with the same title already exists", &id),
+        Ok(None) => {},
+        Err(e) => {
+            error!("Could not check for duplicates: {e:?}");
+            return KError::db_error();
+        }
+    }
+
+    let collection: mongodb::Collection<WithOID<AnimeSeries>> =
+        app.mongodb.database(DB_NAME).collection(COLL_NAME);
+    // another anime may have taken the slug since the deletion
+    let id = anime.id.clone();
+    let mut restored = anime.into_inner();
+    // backups of animes never backfilled have none
+    restored.normalized_titles = normalize_titles(&restored.titles);
+    match restore_slug(&mut restored, &collection.clone_with_type()).await {
+        Ok(()) => {},
+        Err(e) => {
+            error!("Could not pick a slug for the restored anime: {e:?}");
+            return KError::db_error();
+        }
+    }
+    let anime = WithID::new(id, restored);
+    if let Err(e) = collection.insert_one(WithOID::from(anime.clone()), None).await {
+        if is_duplicate_id(&e) {
+            return KError::conflict_with("An anime with this ID already exists", &anime.id);
+        }
+        if is_duplicate_title(&e) {
+            return match find_duplicate(&anime.as_ref().titles, Some(&anime_id), &app).await {
+                Ok(Some(id)) => KError::conflict_with("An anime with the same title already exists", &id),
+                _ => KError::conflict("An anime with the same title already exists")
+            };
+        }
+        if let Some(source) = duplicate_external_id(&e) {
+            return external_id_conflict(source, Some(&anime.as_ref().external_ids), Some(&anime_id), &app).await;
+        }
+        error!("Could not restore anime: {e:?}");
+        return KError::db_error();
+    }
+    restore_cached_images(anime.as_ref().poster.key(), &app.cache_folder, &app.cache_folder.join(BACKUP_FOLDER));
+    if let Err(e) = send_anime_to_meili(anime.clone().into(), &app).await {
+        warn!("Could not add restored anime to meilisearch: {e:?}");
+    }
+    if let Err(e) = seo::build_sitemap(&app).await {
+        warn!("Could not rebuild sitemap: {e:?}");
+    }
+    if let Err(e) = std::fs::remove_file(&backup_path) {
+        warn!("Could not remove backup file of restored anime: {e:?}");
+    }
+
+    HttpResponse::Created()
+        .insert_header((header::LOCATION, format!("/anime/{}", anime.id)))
+        .json(anime)
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct IndexSettings {
+    searchable_attributes: Option<Vec<String>>,
+    filterable_attributes: Option<Vec<String>>,
+    sortable_attributes: Option<Vec<String>>,
+}
+
+async fn apply_index_settings(settings: &IndexSettings, app: &AppState) -> Result<Task> {
+    let settings = Settings {
+        searchable_attributes: settings.searchable_attributes.clone(),
+        filterable_attributes: settings.filterable_attributes.clone(),
+        sortable_attributes: settings.sortable_attributes.clone(),
+        ..Settings::default()
+    };
+    let task = app.meilisearch.get_index(ANIMES_INDEX).await?
+        .set_settings(&settings).await?
+        .wait_for_completion(&app.meilisearch, None, None).await?;
+    Ok(task)
+}
+
+async fn update_index_settings(settings: Json<IndexSettings>, app: Data<AppState>) -> HttpResponse {
+    if settings.searchable_attributes.is_none() && settings.filterable_attributes.is_none()
+        && settings.sortable_attributes.is_none() {
+        return KError::bad_request("Settings are empty");
+    }
+    match apply_index_settings(&settings, &app).await {
+        Ok(task) if task.is_failure() => {
+            let e = task.unwrap_failure();
+            warn!(target: "meilisearch", "Could not apply index settings: {e:?}");
+            KError::bad_request(&e.error_message)
+        },
+        Ok(_) => {
+            info!(target: "meilisearch", "Updated settings of index `{ANIMES_INDEX}`");
+            HttpResponse::NoContent().finish()
+        },
+        Err(e) => {
+            error!("Could not update index settings: {e:?}");
+            KError::internal_error("Could not update index settings")
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct IndexStatsResponse {
+    number_of_documents: usize,
+    is_indexing: bool,
+    field_distribution: std::collections::HashMap<String, usize>,
+}
+
+async fn fetch_index_stats(app: Data<AppState>) -> HttpResponse {
+    let stats = match app.meilisearch.get_index(ANIMES_INDEX).await {
+        Ok(index) => index.get_stats().await,
+        Err(e) => Err(e)
+    };
+    match stats {
+        Ok(stats) => HttpResponse::Ok().json(IndexStatsResponse {
+            number_of_documents: stats.number_of_documents,
+            is_indexing: stats.is_indexing,
+            field_distribution: stats.field_distribution,
+        }),
+        Err(e) => {
+            error!("Could not retrieve index stats: {e:?}");
+            KError::internal_error("Could not retrieve index stats")
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct MeiliIdentifier {
+    id: String,
+}
+
+async fn collect_mongodb_ids(app: &AppState) -> Result<BTreeSet<String>> {
+    let collection: mongodb::Collection<Identifier> =
+        app.mongodb.database(DB_NAME).collection(COLL_NAME);
+    let ids: Vec<Identifier> = collection
+        .find(doc! {}, FindOptions::builder()
+            .projection(doc! { "_id": 1 })
+            .batch_size(ANIMES_VERIFY_BATCH_SIZE as u32)
+            .build())
+        .await.context("Listing MongoDB ids")?
+        .try_collect().await.context("Collecting MongoDB ids")?;
+    Ok(ids.into_iter().map(|i| i.id).collect())
+}
+
+async fn collect_meilisearch_ids(app: &AppState) -> Result<BTreeSet<String>> {
+    let index = app.meilisearch.get_index(ANIMES_INDEX).await?;
+    let mut ids = BTreeSet::new();
+    let mut offset = 0;
+    loop {
+        let page = DocumentsQuery::new(&index)
+            .with_offset(offset)
+            .with_limit(ANIMES_VERIFY_BATCH_SIZE)
+            .with_fields([ANIME_PRIMARY_KEY])
+            .execute::<MeiliIdentifier>()
+            .await?;
+        let fetched = page.results.len();
+        ids.extend(page.results.into_iter().map(|d| d.id));
+        offset += fetched;
+        if fetched < ANIMES_VERIFY_BATCH_SIZE {
+            break;
+        }
+    }
+    Ok(ids)
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct IndexVerifyResponse {
+    missing_from_meilisearch: Vec<String>,
+    missing_from_mongodb: Vec<String>,
+}
+
+async fn verify_index(app: Data<AppState>) -> HttpResponse {
+    let mongodb_ids = match collect_mongodb_ids(&app).await {
+        Ok(ids) => ids,
+        Err(e) => {
+            error!("Could not list MongoDB ids: {e:?}");
+            return KError::db_error();
+        }
+    };
+    let meilisearch_ids = match collect_meilisearch_ids(&app).await {
+        Ok(ids) => ids,
+        Err(e) => {
+            error!("Could not list Meilisearch ids: {e:?}");
+            return KError::internal_error("Could not list indexed documents");
+        }
+    };
+    HttpResponse::Ok().json(IndexVerifyResponse {
+        missing_from_meilisearch: mongodb_ids.difference(&meilisearch_ids).cloned().collect(),
+        missing_from_mongodb: meilisearch_ids.difference(&mongodb_ids).cloned().collect(),
+    })
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig, auth: &AuthConfig) {
     cfg.service(web::resource("/search")
         .guard(guard::Header("content-type", "application/json"))
         .route(web::post().to(search_anime_json)));
     cfg.service(web::resource("/search")
         .guard(guard::Header("content-type", "application/x-www-form-urlencoded"))
         .route(web::post().to(search_anime_form)));
+    cfg.service(web::resource("/search")
+        .route(options("POST, OPTIONS")));
+    cfg.service(web::resource("/search/suggest")
+        .route(web::get().to(suggest_animes))
+        .route(web::head().to(suggest_animes))
+        .route(options("GET, HEAD, OPTIONS")));
 
     let admin_only = RequireRoleGuard(Role::Admin);
     cfg.service(web::resource("/s/anime")
-        .route(web::post().guard(admin_only).to(push_anime)));
+        .guard(guard::Header("content-type", "application/json"))
+        .route(web::post().guard(admin_only).to(push_anime_json)));
+    cfg.service(web::resource("/s/anime")
+        .route(web::post().guard(admin_only).to(push_anime))
+        .route(options("POST, OPTIONS")));
+
+    cfg.service(web::resource("/s/anime/reindex")
+        .route(web::post().guard(admin_only).to(reindex))
+        .route(options("POST, OPTIONS")));
+    cfg.service(web::resource("/s/anime/sync")
+        .route(web::post().guard(RequireMinRoleGuard(auth.sync_min_role())).to(sync))
+        .route(options("POST, OPTIONS")));
+    cfg.service(web::resource("/s/anime/index/settings")
+        .route(web::post().guard(admin_only).to(update_index_settings))
+        .route(options("POST, OPTIONS")));
+    cfg.service(web::resource("/s/anime/index/stats")
+        .route(web::get().guard(admin_only).to(fetch_index_stats))
+        .route(web::head().guard(admin_only).to(fetch_index_stats))
+        .route(options("GET, HEAD, OPTIONS")));
+    cfg.service(web::resource("/s/anime/index/verify")
+        .route(web::get().guard(admin_only).to(verify_index))
+        .route(web::head().guard(admin_only).to(verify_index))
+        .route(options("GET, HEAD, OPTIONS")));
+
+    cfg.service(web::resource("/s/anime/bulk-delete")
+        .route(web::post().guard(admin_only).to(bulk_delete_animes))
+        .route(options("POST, OPTIONS")));
+    cfg.service(web::resource("/s/anime/regenerate-presenters")
+        .route(web::post().guard(admin_only).to(regenerate_presenters))
+        .route(options("POST, OPTIONS")));
+    // kept from before jobs were listed under `/s/jobs`
+    let job_viewer = RequireMinRoleGuard(auth.job_viewer_min_role());
+    cfg.service(web::resource("/s/anime/jobs/{id}")
+        .route(web::get().guard(job_viewer).to(fetch_job))
+        .route(web::head().guard(job_viewer).to(fetch_job))
+        .route(options("GET, HEAD, OPTIONS")));
+    cfg.service(web::resource("/s/anime/export")
+        .route(web::get().guard(admin_only).to(export_animes))
+        .route(options("GET, OPTIONS")));
+    cfg.service(web::resource(ANIMES_IMPORT_PATH)
+        .route(web::post().guard(admin_only).to(import_animes))
+        .route(options("POST, OPTIONS")));
+    cfg.service(web::resource("/s/anime/deleted")
+        .route(web::get().guard(admin_only).to(fetch_deleted_animes))
+        .route(web::head().guard(admin_only).to(fetch_deleted_animes))
+        .route(options("GET, HEAD, OPTIONS")));
 
     cfg.service(web::resource("/s/anime/{id}")
         .route(web::patch().guard(admin_only).to(patch_anime))
-        .route(web::delete().guard(admin_only).to(delete_anime)));
+        .route(web::delete().guard(admin_only).to(delete_anime))
+        .route(options("PATCH, DELETE, OPTIONS")));
+
+    cfg.service(web::resource("/s/anime/{id}/undelete")
+        .route(web::post().guard(admin_only).to(undelete_anime))
+        .route(options("POST, OPTIONS")));
+
+    cfg.service(web::resource("/s/anime/{id}/placeholder")
+        .route(web::post().guard(admin_only).to(compute_anime_placeholder))
+        .route(options("POST, OPTIONS")));
+
+    cfg.service(web::resource("/s/anime/{id}/external-ids")
+        .route(web::patch().guard(RequireMinRoleGuard(Role::Mod)).to(update_external_ids))
+        .route(options("PATCH, OPTIONS")));
+
+    cfg.service(web::resource("/s/anime/{id}/mapping/{index}/note")
+        .route(web::put().guard(RequireMinRoleGuard(Role::Mod)).to(update_pinned_note))
+        .route(options("PUT, OPTIONS")));
 
-    cfg.service(fetch_anime_details);
+    cfg.service(web::resource("/anime")
+        .route(web::get().to(fetch_anime_list))
+        .route(web::head().to(fetch_anime_list))
+        .route(options("GET, HEAD, OPTIONS")));
+    cfg.service(web::resource("/anime/by-studio/{name}")
+        .route(web::get().to(fetch_animes_by_studio))
+        .route(web::head().to(fetch_animes_by_studio))
+        .route(options("GET, HEAD, OPTIONS")));
+    cfg.service(web::resource("/anime/by-author/{name}")
+        .route(web::get().to(fetch_animes_by_author))
+        .route(web::head().to(fetch_animes_by_author))
+        .route(options("GET, HEAD, OPTIONS")));
+    cfg.service(web::resource("/anime/recent")
+        .route(web::get().to(fetch_recent_animes))
+        .route(web::head().to(fetch_recent_animes))
+        .route(options("GET, HEAD, OPTIONS")));
+    cfg.service(web::resource("/anime/slug/{slug}")
+        .route(web::get().to(fetch_anime_by_slug))
+        .route(web::head().to(fetch_anime_by_slug))
+        .route(options("GET, HEAD, OPTIONS")));
+    cfg.service(web::resource("/anime/{id}")
+        .route(web::get().to(fetch_anime_details))
+        .route(web::head().to(fetch_anime_details))
+        .route(options("GET, HEAD, OPTIONS")));
+    cfg.service(web::resource("/anime/{id}/seasons")
+        .route(web::get().to(fetch_anime_seasons))
+        .route(web::head().to(fetch_anime_seasons))
+        .route(options("GET, HEAD, OPTIONS")));
+    cfg.service(web::resource("/anime/{id}/locate")
+        .route(web::get().to(locate_in_anime))
+        .route(web::head().to(locate_in_anime))
+        .route(options("GET, HEAD, OPTIONS")));
+    cfg.service(web::resource("/anime/{id}/preview.png")
+        .route(web::get().to(fetch_anime_preview))
+        .route(web::head().to(fetch_anime_preview))
+        .route(options("GET, HEAD, OPTIONS")));
+    cfg.service(web::resource("/anime/{id}/presenter.webp")
+        .route(web::get().to(fetch_anime_presenter))
+        .route(web::head().to(fetch_anime_presenter))
+        .route(options("GET, HEAD, OPTIONS")));
+    cfg.service(web::resource("/anime/by-external/{source}/{id}")
+        .route(web::get().to(fetch_anime_by_external_id))
+        .route(web::head().to(fetch_anime_by_external_id))
+        .route(options("GET, HEAD, OPTIONS")));
 }
@@ -0,0 +1,101 @@
+use std::collections::HashSet;
+use actix_web::{web::{self, Data, Query}, HttpResponse};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use mongodb::bson::{doc, Bson};
+use log::{error, info, warn};
+
+use crate::gen::anime::{find_orphaned_images, OrphanedImage};
+use crate::middlewares::auth::{Role, RequireRoleGuard};
+use crate::types::{AnimeSeries, AppState, KError};
+use super::options;
+
+const DB_NAME: &str = "Kanime3";
+const COLL_NAME: &str = "animes";
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GcQuery {
+    #[serde(default)]
+    apply: bool,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct GcReport {
+    dry_run: bool,
+    scanned: usize,
+    orphaned: usize,
+    deleted: usize,
+    orphaned_bytes: u64,
+}
+
+async fn referenced_keys(app: &AppState) -> Result<HashSet<String>> {
+    let collection: mongodb::Collection<AnimeSeries> =
+        app.mongodb.database(DB_NAME).collection(COLL_NAME);
+    let keys = collection.distinct("poster.key", None, None).await
+        .context("Listing poster keys")?;
+    Ok(keys.into_iter()
+        .filter_map(|key| match key {
+            Bson::String(key) => Some(key),
+            _ => None
+        })
+        .collect())
+}
+
+fn delete_orphans(orphans: &[OrphanedImage]) -> usize {
+    orphans.iter()
+        .filter(|orphan| match std::fs::remove_file(&orphan.path) {
+            Ok(()) => true,
+            Err(e) => {
+                warn!("Could not delete orphaned image `{}`: {e:?}", orphan.path.display());
+                false
+            }
+        })
+        .count()
+}
+
+/// Lists the cached images no anime refers to, they are only deleted with `?apply=true`
+async fn collect_garbage(query: Query<GcQuery>, app: Data<AppState>) -> HttpResponse {
+    let referenced = match referenced_keys(&app).await {
+        Ok(referenced) => referenced,
+        Err(e) => {
+            error!("Could not list referenced posters: {e:?}");
+            return KError::db_error();
+        }
+    };
+    let apply = query.apply;
+    let cache_folder = app.cache_folder.clone();
+    let grace = app.cache.orphan_grace();
+    let report = web::block(move || {
+        let (scanned, orphans) = find_orphaned_images(&referenced, &cache_folder, grace)?;
+        let deleted = if apply { delete_orphans(&orphans) } else { 0 };
+        Ok::<_, anyhow::Error>(GcReport {
+            dry_run: !apply,
+            scanned,
+            orphaned: orphans.len(),
+            deleted,
+            orphaned_bytes: orphans.iter().map(|orphan| orphan.size).sum(),
+        })
+    }).await;
+    match report {
+        Ok(Ok(report)) => {
+            info!("Cache garbage collection found {} orphaned images out of {}, deleted {}",
+                report.orphaned, report.scanned, report.deleted);
+            HttpResponse::Ok().json(report)
+        },
+        Ok(Err(e)) => {
+            error!("Could not scan the cache folder: {e:?}");
+            KError::internal_error("Could not scan the cache folder")
+        },
+        Err(e) => {
+            error!("Could not scan the cache folder: {e:?}");
+            KError::internal_error("Could not scan the cache folder")
+        }
+    }
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/s/cache/gc")
+        .route(web::post().guard(RequireRoleGuard(Role::Admin)).to(collect_garbage))
+        .route(options("POST, OPTIONS")));
+}
@@ -0,0 +1,156 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+use actix_web::{web::{self, Data, Path}, HttpRequest, HttpResponse, HttpResponseBuilder};
+use actix_web::http::header::{self, HeaderValue, Range};
+use log::error;
+
+use crate::gen::anime::{load_fallback_poster, load_poster, PosterFormat, ANIME_POSTER_SIZES};
+use crate::types::{AppState, KError};
+use super::{cache_control, options, CacheKind};
+
+const POSTER_KEY_LENGTH: usize = 20;
+
+/// Poster keys are generated by us, anything else could escape the cache folder
+fn is_poster_key(key: &str) -> bool {
+    key.len() == POSTER_KEY_LENGTH && key.bytes().all(|b| b.is_ascii_alphanumeric())
+}
+
+/// The single byte range asked by the request, `Err` when it cannot be satisfied. Several ranges
+/// are not supported and, like a malformed or conditional (`If-Range`) one, get the whole image.
+fn requested_range(req: &HttpRequest, length: u64) -> Option<Result<(u64, u64), ()>> {
+    if req.headers().contains_key(header::IF_RANGE) {
+        return None;
+    }
+    let range = req.headers().get(header::RANGE)?.to_str().ok()?;
+    match Range::from_str(range).ok()? {
+        Range::Bytes(ranges) => match ranges.as_slice() {
+            [range] => Some(range.to_satisfiable_range(length).ok_or(())),
+            _ => None
+        },
+        Range::Unregistered(..) => None
+    }
+}
+
+/// Completes `res` with the image, or with the requested part of it along a `206`. The part is
+/// taken from the bytes as stored, they must not be encoded afterwards.
+pub fn serve_image(req: &HttpRequest, mut res: HttpResponseBuilder, image: Vec<u8>) -> HttpResponse {
+    res.insert_header((header::ACCEPT_RANGES, "bytes"));
+    let length = image.len() as u64;
+    match requested_range(req, length) {
+        None => res.body(image),
+        Some(Ok((start, end))) => res
+            .status(actix_web::http::StatusCode::PARTIAL_CONTENT)
+            .insert_header((header::CONTENT_RANGE, format!("bytes {start}-{end}/{length}")))
+            .insert_header((header::CONTENT_ENCODING, "identity"))
+            .body(image[start as usize..=end as usize].to_vec()),
+        Some(Err(())) => {
+            let mut res = KError::range_not_satisfiable();
+            if let Ok(value) = HeaderValue::from_str(&format!("bytes */{length}")) {
+                res.headers_mut().insert(header::CONTENT_RANGE, value);
+            }
+            res
+        }
+    }
+}
+
+/// Served in place of a missing image, it is only cached briefly as the actual image may appear
+pub async fn serve_fallback_poster(req: &HttpRequest, fallback: PathBuf, size: &'static str, format: PosterFormat,
+    app: &AppState) -> HttpResponse {
+    let algorithm = app.images.resize_algorithm.into();
+    match web::block(move || load_fallback_poster(&fallback, size, format, algorithm)).await {
+        Ok(Ok(image)) => {
+            let mut res = HttpResponse::Ok();
+            res.content_type(format.content_type())
+                .insert_header(cache_control(app, CacheKind::Details));
+            serve_image(req, res, image)
+        },
+        Ok(Err(e)) => {
+            error!("Could not load fallback poster: {e:?}");
+            KError::internal_error("Could not load poster")
+        },
+        Err(e) => {
+            error!("Could not load fallback poster: {e:?}");
+            KError::internal_error("Could not load poster")
+        }
+    }
+}
+
+async fn fetch_poster(req: HttpRequest, path: Path<(String, String)>, app: Data<AppState>) -> HttpResponse {
+    let (size, file_name) = path.into_inner();
+    let Some(size) = ANIME_POSTER_SIZES.iter().find(|s| **s == size) else {
+        return KError::not_found();
+    };
+    let Some((key, extension)) = file_name.rsplit_once('.') else {
+        return KError::not_found();
+    };
+    let Some(format) = PosterFormat::from_extension(extension) else {
+        return KError::unsupported_media_type("Posters are only available as webp, png or jpg");
+    };
+    if !is_poster_key(key) {
+        return KError::not_found();
+    }
+
+    let key = key.to_string();
+    let cache_folder = app.cache_folder.clone();
+    match web::block(move || load_poster(&key, size, format, &cache_folder)).await {
+        Ok(Ok(Some(image))) => {
+            let mut res = HttpResponse::Ok();
+            res.content_type(format.content_type())
+                .insert_header(cache_control(&app, CacheKind::Presenter));
+            serve_image(&req, res, image)
+        },
+        Ok(Ok(None)) => match &app.images.fallback_poster {
+            Some(fallback) => serve_fallback_poster(&req, fallback.clone(), size, format, &app).await,
+            None => KError::not_found()
+        },
+        Ok(Err(e)) => {
+            error!("Could not load poster: {e:?}");
+            KError::internal_error("Could not load poster")
+        },
+        Err(e) => {
+            error!("Could not load poster: {e:?}");
+            KError::internal_error("Could not load poster")
+        }
+    }
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/images/{size}/{file}")
+        .route(web::get().to(fetch_poster))
+        .route(web::head().to(fetch_poster))
+        .route(options("GET, HEAD, OPTIONS")));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+    use actix_web::http::StatusCode;
+
+    const KNOWN_IMAGE: &str = "assets/templates/AnimePresenter.png";
+
+    async fn serve_known_image(req: HttpRequest) -> HttpResponse {
+        serve_image(&req, HttpResponse::Ok(), std::fs::read(KNOWN_IMAGE).unwrap())
+    }
+
+    #[actix_web::test]
+    async fn serves_requested_range() {
+        let length = std::fs::metadata(KNOWN_IMAGE).unwrap().len();
+        let app = test::init_service(App::new().route("/", web::get().to(serve_known_image))).await;
+
+        let req = test::TestRequest::get().uri("/")
+            .insert_header((header::RANGE, "bytes=0-9"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(res.headers().get(header::CONTENT_RANGE).unwrap(), &format!("bytes 0-9/{length}"));
+        assert_eq!(test::read_body(res).await.len(), 10);
+
+        let req = test::TestRequest::get().uri("/")
+            .insert_header((header::RANGE, format!("bytes={length}-")))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(res.headers().get(header::CONTENT_RANGE).unwrap(), &format!("bytes */{length}"));
+    }
+}
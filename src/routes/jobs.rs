@@ -0,0 +1,51 @@
+use actix_web::{web::{self, Data, Path}, HttpResponse};
+use log::error;
+
+use crate::jobs::JobHandle;
+use crate::config::AuthConfig;
+use crate::middlewares::auth::{Role, RequireRoleGuard, RequireMinRoleGuard};
+use crate::types::{AppState, KError};
+use super::options;
+
+const JOBS_LIST_LIMIT: usize = 50;
+
+async fn fetch_jobs(app: Data<AppState>) -> HttpResponse {
+    match JobHandle::list(&app.redis, JOBS_LIST_LIMIT).await {
+        Ok(jobs) => HttpResponse::Ok().json(jobs),
+        Err(e) => {
+            error!("Could not list jobs: {e:?}");
+            KError::internal_error("Could not list jobs")
+        }
+    }
+}
+
+pub async fn fetch_job(path: Path<String>, app: Data<AppState>) -> HttpResponse {
+    match JobHandle::load(&app.redis, &path.into_inner()).await {
+        Ok(Some(job)) => HttpResponse::Ok().json(job),
+        Ok(None) => KError::not_found(),
+        Err(e) => {
+            error!("Could not load job: {e:?}");
+            KError::internal_error("Could not load job")
+        }
+    }
+}
+
+/// Responds to a job submission, the job can be polled at the given location
+pub fn job_accepted(job: &JobHandle) -> HttpResponse {
+    HttpResponse::Accepted()
+        .insert_header((actix_web::http::header::LOCATION, format!("/s/jobs/{}", job.id)))
+        .json(job)
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig, auth: &AuthConfig) {
+    let admin_only = RequireRoleGuard(Role::Admin);
+    let job_viewer = RequireMinRoleGuard(auth.job_viewer_min_role());
+    cfg.service(web::resource("/s/jobs")
+        .route(web::get().guard(admin_only).to(fetch_jobs))
+        .route(web::head().guard(admin_only).to(fetch_jobs))
+        .route(options("GET, HEAD, OPTIONS")));
+    cfg.service(web::resource("/s/jobs/{id}")
+        .route(web::get().guard(job_viewer).to(fetch_job))
+        .route(web::head().guard(job_viewer).to(fetch_job))
+        .route(options("GET, HEAD, OPTIONS")));
+}
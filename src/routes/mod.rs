@@ -1,8 +1,19 @@
 pub mod anime;
 pub mod seo;
-use actix_web::{web, HttpResponse};
-use actix_web::http::header::ContentType;
-use crate::types::AppState;
+pub mod stats;
+pub mod session;
+pub mod jobs;
+pub mod images;
+pub mod cache;
+pub mod schema;
+use actix_web::{guard, web, HttpRequest, HttpResponse, Route};
+use actix_web::error::{InternalError, JsonPayloadError, UrlencodedError};
+use actix_web::http::header::{self, CacheControl, CacheDirective, ContentType};
+use crate::config::AuthConfig;
+use crate::types::{AppState, KError};
+
+const JSON_PAYLOAD_LIMIT: usize = 64 * 1024; // 64 KiB
+const FORM_PAYLOAD_LIMIT: usize = 16 * 1024; // 16 KiB
 
 pub async fn get_version(data: web::Data<AppState>) -> HttpResponse {
     HttpResponse::Ok()
@@ -10,9 +21,97 @@ pub async fn get_version(data: web::Data<AppState>) -> HttpResponse {
         .body(data.version_info.clone())
 }
 
-pub fn configure(cfg: &mut web::ServiceConfig) {
-    cfg.route("/version", web::get().to(get_version));
+/// Always `200` while the API runs, a Meilisearch outage only degrades the search. The state of
+/// Meilisearch is the one seen by the last health check.
+pub async fn get_health(data: web::Data<AppState>) -> HttpResponse {
+    let meilisearch = data.meilisearch_health.is_healthy();
+    HttpResponse::Ok()
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .json(serde_json::json!({
+            "status": if meilisearch { "ok" } else { "degraded" },
+            "meilisearch": meilisearch,
+        }))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheKind {
+    /// Images served by anime id or poster key, they only change when the anime is patched
+    Presenter,
+    Details,
+    Search,
+}
+
+/// Builds the `Cache-Control` header for the given kind of response using the `[cache]` lifetimes
+pub fn cache_control(app: &AppState, kind: CacheKind) -> CacheControl {
+    let mut directives = vec![CacheDirective::Public];
+    match kind {
+        CacheKind::Presenter => directives.push(CacheDirective::MaxAge(app.cache.images_max_age())),
+        CacheKind::Details => directives.push(CacheDirective::MaxAge(app.cache.details_max_age())),
+        CacheKind::Search => directives.push(CacheDirective::MaxAge(app.cache.search_max_age())),
+    }
+    CacheControl(directives)
+}
+
+/// Answers `OPTIONS` on a resource, advertising the methods registered for it
+pub fn options(methods: &'static str) -> Route {
+    web::route()
+        .guard(guard::Options())
+        .to(move || async move {
+            HttpResponse::NoContent()
+                .insert_header((header::ALLOW, methods))
+                .insert_header((header::ACCESS_CONTROL_ALLOW_METHODS, methods))
+                .finish()
+        })
+}
+
+fn json_error_handler(err: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    let res = match &err {
+        JsonPayloadError::Overflow { .. } | JsonPayloadError::OverflowKnownLength { .. } =>
+            KError::payload_too_large("The request body is too large"),
+        JsonPayloadError::ContentType => KError::bad_request("Expected a JSON request body"),
+        JsonPayloadError::Deserialize(e) => KError::bad_request(&format!("Invalid JSON body: {e}")),
+        _ => KError::bad_request("Could not read the request body"),
+    };
+    InternalError::from_response(err, res).into()
+}
+
+fn form_error_handler(err: UrlencodedError, _req: &HttpRequest) -> actix_web::Error {
+    let res = match &err {
+        UrlencodedError::Overflow { .. } => KError::payload_too_large("The request body is too large"),
+        UrlencodedError::ContentType => KError::bad_request("Expected a form request body"),
+        UrlencodedError::Parse(e) => KError::bad_request(&format!("Invalid form body: {e}")),
+        _ => KError::bad_request("Could not read the request body"),
+    };
+    InternalError::from_response(err, res).into()
+}
+
+/// Paths left out of `http.request_timeout_secs`, dropping them halfway would lose track of what
+/// was already applied
+pub const UNTIMED_PATHS: [&str; 1] = [anime::ANIMES_IMPORT_PATH];
+
+pub fn configure(cfg: &mut web::ServiceConfig, auth: &AuthConfig) {
+    cfg.app_data(web::JsonConfig::default()
+        .limit(JSON_PAYLOAD_LIMIT)
+        .error_handler(json_error_handler));
+    cfg.app_data(web::FormConfig::default()
+        .limit(FORM_PAYLOAD_LIMIT)
+        .error_handler(form_error_handler));
+
+    cfg.service(web::resource("/version")
+        .route(web::get().to(get_version))
+        .route(web::head().to(get_version))
+        .route(options("GET, HEAD, OPTIONS")));
+    cfg.service(web::resource("/health")
+        .route(web::get().to(get_health))
+        .route(web::head().to(get_health))
+        .route(options("GET, HEAD, OPTIONS")));
 
-    anime::configure(cfg);
-    seo::configure(cfg);
+    anime::configure(cfg, auth);
+    seo::configure(cfg, auth);
+    stats::configure(cfg);
+    session::configure(cfg);
+    jobs::configure(cfg, auth);
+    images::configure(cfg);
+    cache::configure(cfg);
+    schema::configure(cfg);
 }
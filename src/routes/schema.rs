@@ -0,0 +1,84 @@
+use actix_web::{web::{self, Data}, HttpResponse};
+use serde_json::{json, Value};
+
+use crate::types::{AppState, EXTERNAL_ID_SOURCES, SEASON_KINDS};
+use super::{cache_control, options, CacheKind};
+
+fn field(kind: &str, required: bool) -> Value {
+    json!({ "type": kind, "required": required })
+}
+
+fn list(items: &str, required: bool) -> Value {
+    json!({ "type": "array", "items": items, "required": required })
+}
+
+/// Shape of the documents accepted by the push and patch endpoints, every field is optional in a
+/// patch. Types are either JSON types or the name of an entry of `types` or `enums`.
+fn anime_schema(app: &AppState) -> Value {
+    let kinds: Vec<&str> = SEASON_KINDS.iter().map(|kind| kind.as_str()).collect();
+    json!({
+        "enums": {
+            "seasonKind": kinds,
+            "externalIdSource": EXTERNAL_ID_SOURCES,
+        },
+        "fields": {
+            "titles": list("title", true),
+            "synonyms": list("string", false),
+            "manga": field("mangaReleaseInfo", true),
+            "anime": field("animeReleaseInfo", true),
+            "mapping": list("seasonMapping", true),
+            "externalIds": field("externalIds", false),
+            "accentColor": field("integer", false),
+        },
+        "types": {
+            "title": {
+                "value": field("string", true),
+                "language": field("string", false),
+                "primary": field("boolean", false),
+            },
+            "mangaReleaseInfo": {
+                "author": field("string", true),
+                "volumes": field("integer", true),
+                "chapters": field("integer", true),
+                "releaseYear": field("integer", true),
+            },
+            "animeReleaseInfo": {
+                "studios": list("string", true),
+                "seasons": field("integer", true),
+                "episodes": field("integer", true),
+                "releaseYear": field("integer", true),
+            },
+            "seasonMapping": {
+                "kind": field("seasonKind", true),
+                "label": field("string", true),
+                "startEpisode": field("integer", true),
+                "endEpisode": field("integer", true),
+                "startChapter": field("integer", true),
+                "endChapter": field("integer", true),
+                "startVolume": field("integer", true),
+                "endVolume": field("integer", true),
+            },
+            "externalIds": {
+                "mal": field("integer", false),
+                "anilist": field("integer", false),
+            },
+        },
+        "limits": {
+            "maxTitles": app.catalog.max_titles(),
+            "maxTitleLength": app.catalog.max_title_length(),
+        }
+    })
+}
+
+async fn fetch_anime_schema(app: Data<AppState>) -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header(cache_control(&app, CacheKind::Details))
+        .json(anime_schema(&app))
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/schema/anime")
+        .route(web::get().to(fetch_anime_schema))
+        .route(web::head().to(fetch_anime_schema))
+        .route(options("GET, HEAD, OPTIONS")));
+}
@@ -1,21 +1,29 @@
-use actix_web::{web::{self, Data}, HttpResponse};
+use actix_web::{web::{self, Data, Path}, HttpRequest, HttpResponse};
+use actix_web::http::header::{self, EntityTag};
 use anyhow::Result;
-use serde::{self, Deserialize};
-use mongodb::options::FindOptions;
-use std::fs::File;
-use std::io::{Write, BufWriter};
+use serde::{self, Deserialize, Serialize};
+use mongodb::options::{FindOneOptions, FindOptions};
+use std::io::Write;
+use sha2::{Digest, Sha256};
 use log::{info, error};
 use futures::stream::TryStreamExt;
 use mongodb::bson::{doc, serde_helpers::hex_string_as_object_id};
 use chrono::{Utc, TimeZone};
 
-use crate::middlewares::auth::{Role, RequireRoleGuard};
-use crate::types::{AppState, KError};
+use crate::jobs::spawn_job;
+use crate::config::AuthConfig;
+use crate::middlewares::auth::RequireMinRoleGuard;
+use crate::types::{AnimeSeries, AppState, KError, WithOID};
+use super::{cache_control, options, CacheKind};
+use super::anime::to_oid;
+use super::jobs::job_accepted;
 
 const DB_NAME: &str = "Kanime3";
 const COLL_NAME: &str = "animes";
 const ANIME_SITEMAP_FILE: &str = "anime_index.xml";
+const ANIME_SITEMAP_STATE_FILE: &str = "anime_index.json";
 const ANIMES_SITEMAP_BATCH_SIZE: u32 = 32;
+const SITEMAP_JOB: &str = "sitemap";
 
 #[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -23,6 +31,8 @@ pub struct Metadata {
     #[serde(rename = "_id")]
     #[serde(with = "hex_string_as_object_id")]
     pub id: String,
+    #[serde(default)]
+    pub slug: Option<String>,
     pub updated_on: u64,
 }
 
@@ -31,6 +41,7 @@ fn write_escaped(out: &mut dyn Write, s: &str) -> Result<()> {
         match chr {
             '&' => write!(out, "&amp;")?,
             '\'' => write!(out, "&apos;")?,
+            '"' => write!(out, "&quot;")?,
             '>' => write!(out, "&gt;")?,
             '<' => write!(out, "&lt;")?,
             c => write!(out, "{c}")?
@@ -39,25 +50,61 @@ fn write_escaped(out: &mut dyn Write, s: &str) -> Result<()> {
     Ok(())
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+struct SitemapState {
+    etag: String,
+    count: u64,
+    last_updated_on: u64,
+}
+
+/// The sitemap only has to be rebuilt when an anime was added, removed or updated
+async fn catalog_version(col: &mongodb::Collection<Metadata>) -> Result<(u64, u64)> {
+    let count = col.count_documents(None, None).await?;
+    let last = col.find_one(None, FindOneOptions::builder()
+            .sort(doc! { "updatedOn": -1 })
+            .projection(doc! { "_id": 1, "updatedOn": 1 })
+            .build())
+        .await?;
+    Ok((count, last.map_or(0, |doc| doc.updated_on)))
+}
+
+fn read_sitemap_state(app: &AppState) -> Option<SitemapState> {
+    let raw = std::fs::read(app.cache_folder.join(ANIME_SITEMAP_STATE_FILE)).ok()?;
+    serde_json::from_slice(&raw).ok()
+}
+
 pub async fn build_sitemap(app: &AppState) -> Result<()> {
     let col: mongodb::Collection<Metadata> =
         app.mongodb.database(DB_NAME).collection(COLL_NAME);
+    let (count, last_updated_on) = catalog_version(&col).await?;
+    let path = app.cache_folder.join(ANIME_SITEMAP_FILE);
+    if let Some(state) = read_sitemap_state(app) {
+        if state.count == count && state.last_updated_on == last_updated_on && path.exists() {
+            info!("Sitemap is already up to date");
+            return Ok(());
+        }
+    }
+
     let mut cursor = col
         .find(None, FindOptions::builder()
             .batch_size(ANIMES_SITEMAP_BATCH_SIZE)
-            .projection(doc! { "_id": 1, "updatedOn": 1 })
+            .projection(doc! { "_id": 1, "slug": 1, "updatedOn": 1 })
             .build())
         .await?;
 
-    let domain = &app.domain;
-    let path = app.cache_folder.clone().join(ANIME_SITEMAP_FILE);
-    let mut f = BufWriter::new(File::create(path)?);
+    let mut f = Vec::new();
     write!(f, r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">"#)?;
     while let Some(doc) = cursor.try_next().await? {
         write!(f, "<url>")?;
         {
-            write!(f, "<loc>https://{domain}/anime/")?;
-            write_escaped(&mut f, &doc.id)?;
+            // animes created before slugs existed are still listed by ID
+            let url = match &doc.slug {
+                Some(slug) => app.site_url(&format!("/anime/slug/{slug}")),
+                None => app.canonical_anime_url(&doc.id)
+            };
+            write!(f, "<loc>")?;
+            write_escaped(&mut f, &url)?;
             write!(f, "</loc>")?;
 
             match Utc.timestamp_millis_opt(doc.updated_on as i64).latest() {
@@ -68,22 +115,144 @@ pub async fn build_sitemap(app: &AppState) -> Result<()> {
         write!(f, "</url>")?;
     }
     write!(f, "</urlset>")?;
+
+    let state = SitemapState {
+        etag: format!("{:x}", Sha256::digest(&f)),
+        count,
+        last_updated_on
+    };
+    std::fs::write(path, f)?;
+    std::fs::write(app.cache_folder.join(ANIME_SITEMAP_STATE_FILE), serde_json::to_vec(&state)?)?;
     info!("Successfully built sitemap");
     Ok(())
 }
 
+async fn fetch_sitemap(req: HttpRequest, app: Data<AppState>) -> HttpResponse {
+    let Some(state) = read_sitemap_state(&app) else {
+        return KError::not_found();
+    };
+    let etag = EntityTag::new_strong(state.etag);
+    let expected = etag.to_string();
+    let cached = match req.headers().get(header::IF_NONE_MATCH).map(|v| v.to_str()) {
+        Some(Ok(value)) => value.split(',').any(|tag| tag.trim() == expected || tag.trim() == "*"),
+        _ => false
+    };
+    if cached {
+        return HttpResponse::NotModified()
+            .insert_header(header::ETag(etag))
+            .finish();
+    }
+
+    let path = app.cache_folder.join(ANIME_SITEMAP_FILE);
+    match web::block(move || std::fs::read(path)).await {
+        Ok(Ok(sitemap)) => HttpResponse::Ok()
+            .content_type("application/xml")
+            .insert_header(header::ETag(etag))
+            .insert_header(cache_control(&app, CacheKind::Details))
+            .body(sitemap),
+        Ok(Err(e)) if e.kind() == std::io::ErrorKind::NotFound => KError::not_found(),
+        Ok(Err(e)) => {
+            error!("Could not read sitemap: {e:?}");
+            KError::internal_error("Could not read sitemap")
+        },
+        Err(e) => {
+            error!("Could not read sitemap: {e:?}");
+            KError::internal_error("Could not read sitemap")
+        }
+    }
+}
+
+fn describe(anime: &AnimeSeries) -> String {
+    let mut description = format!("{} episodes over {} seasons", anime.anime.episodes,
+        anime.anime.seasons);
+    if !anime.anime.studios.is_empty() {
+        description.push_str(" by ");
+        description.push_str(&anime.anime.studios.join(", "));
+    }
+    description.push_str(&format!(", released in {}. Manga by {}, {} volumes and {} chapters since {}.",
+        anime.anime.release_year, anime.manga.author, anime.manga.volumes, anime.manga.chapters,
+        anime.manga.release_year));
+    description
+}
+
+fn write_meta(out: &mut dyn Write, property: &str, content: &str) -> Result<()> {
+    write!(out, r#"<meta property="{property}" content=""#)?;
+    write_escaped(out, content)?;
+    write!(out, r#"">"#)?;
+    Ok(())
+}
+
+fn build_embed(url: &str, anime: &AnimeSeries) -> Result<Vec<u8>> {
+    let title = anime.primary_title().unwrap_or_default();
+    let mut out = Vec::new();
+    write!(out, r#"<!DOCTYPE html><html><head><meta charset="utf-8"><title>"#)?;
+    write_escaped(&mut out, title)?;
+    write!(out, "</title>")?;
+    write_meta(&mut out, "og:type", "website")?;
+    write_meta(&mut out, "og:title", title)?;
+    write_meta(&mut out, "og:description", &describe(anime))?;
+    write_meta(&mut out, "og:url", url)?;
+    write_meta(&mut out, "og:image", &format!("{url}/presenter.webp"))?;
+    write_meta(&mut out, "twitter:card", "summary_large_image")?;
+    write!(out, r#"<meta http-equiv="refresh" content="0; url="#)?;
+    write_escaped(&mut out, url)?;
+    write!(out, r#""></head><body></body></html>"#)?;
+    Ok(out)
+}
+
+async fn fetch_anime_embed(req: HttpRequest, path: Path<String>, app: Data<AppState>) -> HttpResponse {
+    let anime_id = match to_oid(&path.into_inner()) {
+        Ok(anime_id) => anime_id,
+        Err(e) => return KError::bad_request(&e.to_string())
+    };
+    let col: mongodb::Collection<WithOID<AnimeSeries>> =
+        app.mongodb.database(DB_NAME).collection(COLL_NAME);
+    let anime = match col.find_one(doc! { "_id": anime_id }, None).await {
+        Ok(Some(anime)) => anime,
+        Ok(None) => return KError::not_found(),
+        Err(e) => {
+            error!("Could not find anime: {e:?}");
+            return KError::db_error();
+        }
+    };
+    match build_embed(&app.request_site_url(&req, &format!("/anime/{}", anime.id)), anime.as_ref()) {
+        Ok(html) => HttpResponse::Ok()
+            .content_type("text/html; charset=utf-8")
+            .insert_header(cache_control(&app, CacheKind::Details))
+            .body(html),
+        Err(e) => {
+            error!("Could not build anime embed: {e:?}");
+            KError::internal_error("Could not build anime embed")
+        }
+    }
+}
+
 async fn update_sitemap(app: Data<AppState>) -> HttpResponse {
-    match build_sitemap(&app).await {
-        Ok(()) => HttpResponse::NoContent().finish(),
+    let job_app = app.clone();
+    let job = spawn_job(&app.redis, SITEMAP_JOB, move |_| async move {
+        build_sitemap(&job_app).await
+    }).await;
+    match job {
+        Ok(job) => job_accepted(&job),
         Err(e) => {
-            error!("Could not generate anime index sitemap: {e:?}");
+            error!("Could not start anime index sitemap generation: {e:?}");
             KError::internal_error("Could not generate anime index sitemap")
         }
     }
 }
 
-pub fn configure(cfg: &mut web::ServiceConfig) {
-    let admin_only = RequireRoleGuard(Role::Admin);
+pub fn configure(cfg: &mut web::ServiceConfig, auth: &AuthConfig) {
     cfg.service(web::resource("/s/seo/sitemap")
-        .route(web::post().guard(admin_only).to(update_sitemap)));
+        .route(web::post().guard(RequireMinRoleGuard(auth.seo_min_role())).to(update_sitemap))
+        .route(options("POST, OPTIONS")));
+
+    cfg.service(web::resource("/sitemap/anime.xml")
+        .route(web::get().to(fetch_sitemap))
+        .route(web::head().to(fetch_sitemap))
+        .route(options("GET, HEAD, OPTIONS")));
+
+    cfg.service(web::resource("/anime/{id}/embed")
+        .route(web::get().to(fetch_anime_embed))
+        .route(web::head().to(fetch_anime_embed))
+        .route(options("GET, HEAD, OPTIONS")));
 }
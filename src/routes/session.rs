@@ -0,0 +1,141 @@
+use std::time::SystemTime;
+use actix_web::{web::{self, Data, Json, ReqData}, HttpRequest, HttpResponse};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use redis::AsyncCommands;
+use log::{error, info, warn};
+
+use crate::middlewares::auth::{generate_token, Role, Session};
+use crate::types::{AppState, KError};
+use super::options;
+
+const BOOTSTRAP_KEY_HEADER: &str = "X-Bootstrap-Key";
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct SessionRequest {
+    user_id: String,
+    role: Role,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct IssuedSession {
+    token: String,
+    expires_on: u64,
+}
+
+/// Sessions can only be issued in debug mode or with the configured bootstrap key
+fn can_issue_sessions(req: &HttpRequest, app: &AppState) -> bool {
+    if app.debug {
+        return true;
+    }
+    match (app.auth.bootstrap_key.as_deref(), req.headers().get(BOOTSTRAP_KEY_HEADER)) {
+        (Some(expected), Some(provided)) => provided.as_bytes() == expected.as_bytes(),
+        _ => false
+    }
+}
+
+async fn store_session(session: &Session, app: &AppState) -> Result<()> {
+    let ttl = app.auth.session_ttl_secs() as usize;
+    let mut con = app.redis.get().await?;
+    con.set_ex::<_, _, ()>(format!("{}:{}", app.auth.token_prefix(), session.token),
+        serde_json::to_string(session)?, ttl).await?;
+    let index = format!("user:{}:tokens", session.user_id);
+    con.sadd::<_, _, ()>(&index, &session.token).await?;
+    con.expire::<_, ()>(&index, ttl).await?;
+    Ok(())
+}
+
+async fn issue_session(req: HttpRequest, body: Json<SessionRequest>, app: Data<AppState>) -> HttpResponse {
+    if !can_issue_sessions(&req, &app) {
+        return KError::forbidden();
+    }
+    let body = body.into_inner();
+    if body.user_id.is_empty() {
+        return KError::bad_request("A user ID is required");
+    }
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .expect("The time can never be earlier than the Unix epoch")
+        .as_millis() as u64;
+    let session = Session {
+        token: generate_token(app.auth.token_length()),
+        expires_on: now + app.auth.session_ttl_secs() * 1000,
+        user_id: body.user_id,
+        role: body.role
+    };
+    match store_session(&session, &app).await {
+        Ok(()) => {
+            info!("Issued a {:?} session for user `{}`", session.role, session.user_id);
+            HttpResponse::Created().json(IssuedSession {
+                token: session.token,
+                expires_on: session.expires_on
+            })
+        },
+        Err(e) => {
+            error!("Could not store session: {e:?}");
+            KError::internal_error("Could not store session")
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct CurrentSession {
+    user_id: String,
+    role: Role,
+    expires_on: u64,
+}
+
+async fn fetch_current_session(session: Option<ReqData<Session>>) -> HttpResponse {
+    match session {
+        Some(session) => HttpResponse::Ok().json(CurrentSession {
+            user_id: session.user_id.clone(),
+            role: session.role,
+            expires_on: session.expires_on
+        }),
+        None => KError::unauthorized()
+    }
+}
+
+async fn delete_session(session: &Session, app: &AppState) -> Result<()> {
+    let mut con = app.redis.get().await?;
+    con.del::<_, ()>(format!("{}:{}", app.auth.token_prefix(), session.token)).await?;
+    let index = format!("user:{}:tokens", session.user_id);
+    if let Err(e) = con.srem::<_, _, ()>(&index, &session.token).await {
+        // the token is already gone, the index entry only lingers until it expires
+        warn!("Could not remove token from the index of user `{}`: {e:?}", session.user_id);
+    }
+    Ok(())
+}
+
+/// Revokes the token the request was made with
+async fn revoke_current_session(session: Option<ReqData<Session>>, app: Data<AppState>) -> HttpResponse {
+    let Some(session) = session else {
+        return KError::unauthorized()
+    };
+    match delete_session(&session, &app).await {
+        Ok(()) => {
+            app.session_cache.invalidate(&session.token);
+            info!("Revoked a session of user `{}`", session.user_id);
+            HttpResponse::NoContent().finish()
+        },
+        Err(e) => {
+            error!("Could not revoke session: {e:?}");
+            KError::internal_error("Could not revoke session")
+        }
+    }
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/s/session")
+        .route(web::post().to(issue_session))
+        .route(options("POST, OPTIONS")));
+
+    cfg.service(web::resource("/auth/me")
+        .route(web::get().to(fetch_current_session))
+        .route(web::head().to(fetch_current_session))
+        .route(web::delete().to(revoke_current_session))
+        .route(options("GET, HEAD, DELETE, OPTIONS")));
+}
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+use actix_web::{web::{self, Data}, HttpResponse};
+use actix_web::http::header::ContentType;
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use futures::stream::TryStreamExt;
+use mongodb::bson::{self, doc};
+use redis::AsyncCommands;
+use log::{error, warn};
+
+use crate::middlewares::auth::{Role, RequireRoleGuard};
+use crate::types::{AppState, KError};
+use super::options;
+
+const DB_NAME: &str = "Kanime3";
+const COLL_NAME: &str = "animes";
+const STATS_REDIS_KEY: &str = "stats:catalog";
+const STATS_TTL_SECS: usize = 60;
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+struct Totals {
+    count: u64,
+    episodes: u64,
+    last_updated_on: Option<u64>,
+}
+
+#[derive(Deserialize, Debug)]
+struct KindCount {
+    #[serde(rename = "_id")]
+    kind: String,
+    count: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct StatsFacets {
+    totals: Vec<Totals>,
+    kinds: Vec<KindCount>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogStats {
+    count: u64,
+    episodes: u64,
+    last_updated_on: Option<u64>,
+    kinds: HashMap<String, u64>,
+}
+
+async fn compute_stats(app: &AppState) -> Result<CatalogStats> {
+    let col: mongodb::Collection<bson::Document> =
+        app.mongodb.database(DB_NAME).collection(COLL_NAME);
+    let pipeline = [doc! {
+        "$facet": {
+            "totals": [{
+                "$group": {
+                    "_id": null,
+                    "count": { "$sum": 1 },
+                    "episodes": { "$sum": "$anime.episodes" },
+                    "lastUpdatedOn": { "$max": "$updatedOn" }
+                }
+            }],
+            "kinds": [
+                { "$unwind": "$mapping" },
+                { "$group": { "_id": "$mapping.kind", "count": { "$sum": 1 } } }
+            ]
+        }
+    }];
+    let facets = col.aggregate(pipeline, None).await?
+        .try_next().await?
+        .ok_or_else(|| anyhow!("Aggregation returned no result"))?;
+    let facets: StatsFacets = bson::from_document(facets)?;
+
+    let totals = facets.totals.into_iter().next().unwrap_or_default();
+    Ok(CatalogStats {
+        count: totals.count,
+        episodes: totals.episodes,
+        last_updated_on: totals.last_updated_on,
+        kinds: facets.kinds.into_iter().map(|k| (k.kind, k.count)).collect()
+    })
+}
+
+async fn get_cached_stats(app: &AppState) -> Result<Option<String>> {
+    let mut con = app.redis.get().await?;
+    Ok(con.get(STATS_REDIS_KEY).await?)
+}
+
+async fn cache_stats(app: &AppState, stats: &str) -> Result<()> {
+    let mut con = app.redis.get().await?;
+    con.set_ex::<_, _, ()>(STATS_REDIS_KEY, stats, STATS_TTL_SECS).await?;
+    Ok(())
+}
+
+async fn get_stats(app: Data<AppState>) -> HttpResponse {
+    match get_cached_stats(&app).await {
+        Ok(Some(stats)) => return HttpResponse::Ok()
+            .insert_header(ContentType::json())
+            .body(stats),
+        Ok(None) => {},
+        Err(e) => warn!("Could not read cached stats: {e:?}")
+    }
+
+    let stats = match compute_stats(&app).await {
+        Ok(stats) => serde_json::to_string(&stats)
+            .expect("Stats should always be serializable"),
+        Err(e) => {
+            error!("Could not compute catalog stats: {e:?}");
+            return KError::db_error();
+        }
+    };
+    if let Err(e) = cache_stats(&app, &stats).await {
+        warn!("Could not cache stats: {e:?}");
+    }
+    HttpResponse::Ok()
+        .insert_header(ContentType::json())
+        .body(stats)
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    let admin_only = RequireRoleGuard(Role::Admin);
+    cfg.service(web::resource("/s/stats")
+        .route(web::get().guard(admin_only).to(get_stats))
+        .route(web::head().guard(admin_only).to(get_stats))
+        .route(options("GET, HEAD, OPTIONS")));
+}
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::middlewares::auth::Session;
+
+struct CachedSession {
+    session: Session,
+    cached_on: Instant,
+    last_used: u64,
+}
+
+struct Entries {
+    sessions: HashMap<String, CachedSession>,
+    // incremented on every use, the entry with the lowest value is the least recently used
+    clock: u64,
+}
+
+/// Sessions recently read from Redis, shared by every worker. Entries are kept for `ttl` at most
+/// and never past the expiry of their session, the least recently used ones are evicted once
+/// `capacity` is reached. A zero TTL or capacity disables the cache.
+#[derive(Clone)]
+pub struct SessionCache {
+    entries: Arc<Mutex<Entries>>,
+    ttl: Duration,
+    capacity: usize,
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("The time can never be earlier than the Unix epoch")
+        .as_millis() as u64
+}
+
+impl SessionCache {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        let entries = Entries { sessions: HashMap::new(), clock: 0 };
+        Self { entries: Arc::new(Mutex::new(entries)), ttl, capacity }
+    }
+
+    fn is_enabled(&self) -> bool {
+        !self.ttl.is_zero() && self.capacity > 0
+    }
+
+    pub fn get(&self, token: &str) -> Option<Session> {
+        if !self.is_enabled() {
+            return None;
+        }
+        let mut entries = self.entries.lock().expect("The session cache lock is never poisoned");
+        entries.clock += 1;
+        let clock = entries.clock;
+        let cached = entries.sessions.get_mut(token)?;
+        if cached.cached_on.elapsed() >= self.ttl || cached.session.expires_on <= now_ms() {
+            entries.sessions.remove(token);
+            return None;
+        }
+        cached.last_used = clock;
+        Some(cached.session.clone())
+    }
+
+    pub fn insert(&self, session: Session) {
+        if !self.is_enabled() {
+            return;
+        }
+        let mut entries = self.entries.lock().expect("The session cache lock is never poisoned");
+        entries.clock += 1;
+        let clock = entries.clock;
+        if !entries.sessions.contains_key(&session.token) && entries.sessions.len() >= self.capacity {
+            let oldest = entries.sessions.iter()
+                .min_by_key(|(_, cached)| cached.last_used)
+                .map(|(token, _)| token.clone());
+            if let Some(oldest) = oldest {
+                entries.sessions.remove(&oldest);
+            }
+        }
+        let token = session.token.clone();
+        entries.sessions.insert(token, CachedSession { session, cached_on: Instant::now(), last_used: clock });
+    }
+
+    pub fn invalidate(&self, token: &str) {
+        let mut entries = self.entries.lock().expect("The session cache lock is never poisoned");
+        entries.sessions.remove(token);
+    }
+}
@@ -0,0 +1,82 @@
+use std::{fmt, fs::File, io::{self, BufReader}, net::ToSocketAddrs, sync::Arc, time::Duration};
+use actix_http::{body::MessageBody, HttpService, Protocol, Request, Response, error::DispatchError};
+use actix_server::{Server, ServerBuilder};
+use actix_service::{fn_service, map_config, IntoServiceFactory, ServiceFactory, ServiceFactoryExt};
+use actix_web::{dev::AppConfig, Error};
+use tokio_rustls::TlsAcceptor;
+use rustls::{Certificate, PrivateKey, ServerConfig};
+use anyhow::{Context, Result, anyhow};
+
+use crate::config::TlsConfig;
+
+// TLS is terminated with the rustls version the MongoDB driver already depends on, and the
+// decrypted stream is handed to actix-http directly, like `HttpServer::listen` does for TCP.
+
+pub fn load_server_config(tls: &TlsConfig) -> Result<ServerConfig> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(tls.cert)
+            .with_context(|| format!("Opening TLS certificate `{}`", tls.cert))?))
+        .with_context(|| format!("Reading TLS certificate `{}`", tls.cert))?;
+    if certs.is_empty() {
+        return Err(anyhow!("No certificate found in `{}`", tls.cert));
+    }
+
+    let mut key_file = BufReader::new(File::open(tls.key)
+        .with_context(|| format!("Opening TLS private key `{}`", tls.key))?);
+    let key = loop {
+        match rustls_pemfile::read_one(&mut key_file)
+            .with_context(|| format!("Reading TLS private key `{}`", tls.key))? {
+            Some(rustls_pemfile::Item::PKCS8Key(key) | rustls_pemfile::Item::RSAKey(key)
+                 | rustls_pemfile::Item::ECKey(key)) => break key,
+            Some(_) => continue,
+            None => return Err(anyhow!("No private key found in `{}`", tls.key)),
+        }
+    };
+
+    let mut config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs.into_iter().map(Certificate).collect(), PrivateKey(key))
+        .context("Invalid TLS certificate or private key")?;
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+pub fn bind_rustls<F, I, S, B>(addr: impl ToSocketAddrs, config: ServerConfig, factory: F,
+    workers: Option<usize>, keep_alive: Option<Duration>) -> io::Result<Server>
+    where
+        F: Fn() -> I + Send + Clone + 'static,
+        I: IntoServiceFactory<S, Request>,
+        S: ServiceFactory<Request, Config = AppConfig> + 'static,
+        S::Error: Into<Error> + 'static,
+        S::InitError: fmt::Debug,
+        S::Response: Into<Response<B>> + 'static,
+        B: MessageBody + 'static,
+{
+    let acceptor = TlsAcceptor::from(Arc::new(config));
+    let mut builder = ServerBuilder::default();
+    if let Some(workers) = workers {
+        builder = builder.workers(workers);
+    }
+    let server = builder.bind("kanime-api-v3-tls", addr, move || {
+        let acceptor = acceptor.clone();
+        let mut svc = HttpService::build();
+        if let Some(keep_alive) = keep_alive {
+            svc = svc.keep_alive(keep_alive);
+        }
+        let fac = factory()
+            .into_factory()
+            .map_err(|err| err.into().error_response());
+        let http = svc.finish(map_config(fac, |_| AppConfig::default()));
+
+        fn_service(move |io: actix_web::rt::net::TcpStream| {
+            let acceptor = acceptor.clone();
+            async move {
+                let peer_addr = io.peer_addr().ok();
+                let stream = acceptor.accept(io).await.map_err(DispatchError::Io)?;
+                Ok((stream, Protocol::Http1, peer_addr))
+            }
+        })
+        .and_then(http)
+    })?;
+    Ok(server.run())
+}
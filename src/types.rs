@@ -1,21 +1,74 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::collections::HashMap;
 use std::path::PathBuf;
-use actix_web::HttpResponse;
+use actix_web::{HttpRequest, HttpResponse};
 use mongodb::bson::{self, serde_helpers::hex_string_as_object_id};
 use serde::{Serialize, Deserialize};
 use serde_json::json;
+use crate::middlewares::ip::request_scheme;
+use crate::config::{DEFAULT_SCHEME, AuthConfig, CacheConfig, CatalogConfig, ImagesConfig, ListConfig,
+    SearchConfig};
+use crate::gen::ImageJobs;
+use crate::pool::RedisPool;
+use crate::session_cache::SessionCache;
+use crate::health::MeilisearchHealth;
 
 pub struct AppState {
     pub app_name: String,
+    pub debug: bool,
     pub domain: String,
+    pub scheme: Option<String>,
     pub version_info: String,
     pub mongodb: mongodb::Client,
     pub meilisearch: meilisearch_sdk::Client,
-    pub redis: redis::Client,
-    pub cache_folder: PathBuf
+    pub meilisearch_timeout: Duration,
+    pub meilisearch_health: MeilisearchHealth,
+    pub redis: RedisPool,
+    pub cache_folder: PathBuf,
+    pub images: ImagesConfig,
+    pub image_jobs: ImageJobs,
+    pub auth: AuthConfig,
+    pub session_cache: SessionCache,
+    pub cache: CacheConfig,
+    pub search: SearchConfig,
+    pub catalog: CatalogConfig,
+    pub list: ListConfig
 }
 
+impl AppState {
+    /// Public URL of the given path on the configured domain, `path` starts with a slash
+    pub fn site_url(&self, path: &str) -> String {
+        let scheme = self.scheme.as_deref().unwrap_or(DEFAULT_SCHEME);
+        format!("{scheme}://{}{path}", self.domain)
+    }
+
+    /// Same as [`Self::site_url`] but follows the scheme of the request if none is configured
+    pub fn request_site_url(&self, req: &HttpRequest, path: &str) -> String {
+        let scheme = self.scheme.as_deref().unwrap_or_else(|| request_scheme(req));
+        format!("{scheme}://{}{path}", self.domain)
+    }
+
+    pub fn canonical_anime_url(&self, id: &str) -> String {
+        self.site_url(&format!("/anime/{id}"))
+    }
+}
+
+/// Numeric codes are the HTTP status followed by a two digit index, they never change once
+/// published:
+///
+/// | Type                     | Code  |
+/// |--------------------------|-------|
+/// | `bad_request`            | 40001 |
+/// | `unauthorized`           | 40101 |
+/// | `forbidden`              | 40301 |
+/// | `not_found`              | 40401 |
+/// | `conflict`               | 40901 |
+/// | `payload_too_large`      | 41301 |
+/// | `unsupported_media_type` | 41501 |
+/// | `range_not_satisfiable`  | 41601 |
+/// | `internal_error`         | 50001 |
+/// | `service_unavailable`    | 50301 |
+/// | `gateway_timeout`        | 50401 |
 #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
 #[serde(rename_all = "snake_case")]
 pub enum KErrorType {
@@ -23,6 +76,31 @@ pub enum KErrorType {
     BadRequest,
     InternalError,
     NotFound,
+    UnsupportedMediaType,
+    PayloadTooLarge,
+    Conflict,
+    Unauthorized,
+    ServiceUnavailable,
+    GatewayTimeout,
+    RangeNotSatisfiable,
+}
+
+impl KErrorType {
+    pub fn code(self) -> u32 {
+        match self {
+            KErrorType::BadRequest => 40001,
+            KErrorType::Unauthorized => 40101,
+            KErrorType::Forbidden => 40301,
+            KErrorType::NotFound => 40401,
+            KErrorType::Conflict => 40901,
+            KErrorType::PayloadTooLarge => 41301,
+            KErrorType::UnsupportedMediaType => 41501,
+            KErrorType::RangeNotSatisfiable => 41601,
+            KErrorType::InternalError => 50001,
+            KErrorType::ServiceUnavailable => 50301,
+            KErrorType::GatewayTimeout => 50401,
+        }
+    }
 }
 
 pub struct KError;
@@ -31,6 +109,7 @@ impl KError {
     pub fn bad_request(details: &'_ str) -> HttpResponse {
         HttpResponse::BadRequest().json(json!({
             "error": KErrorType::BadRequest,
+            "code": KErrorType::BadRequest.code(),
             "errorDescription": details,
         }))
     }
@@ -38,6 +117,7 @@ impl KError {
     pub fn not_found() -> HttpResponse {
         HttpResponse::NotFound().json(json!({
             "error": KErrorType::NotFound,
+            "code": KErrorType::NotFound.code(),
             "errorDescription": "Not Found",
         }))
     }
@@ -45,20 +125,89 @@ impl KError {
     pub fn internal_error(details: &'_ str) -> HttpResponse {
         HttpResponse::InternalServerError().json(json!({
             "error": KErrorType::InternalError,
+            "code": KErrorType::InternalError.code(),
+            "errorDescription": details,
+        }))
+    }
+
+    pub fn service_unavailable(details: &'_ str) -> HttpResponse {
+        HttpResponse::ServiceUnavailable().json(json!({
+            "error": KErrorType::ServiceUnavailable,
+            "code": KErrorType::ServiceUnavailable.code(),
             "errorDescription": details,
         }))
     }
 
+    pub fn gateway_timeout() -> HttpResponse {
+        HttpResponse::GatewayTimeout().json(json!({
+            "error": KErrorType::GatewayTimeout,
+            "code": KErrorType::GatewayTimeout.code(),
+            "errorDescription": "The request took too long to complete",
+        }))
+    }
+
     pub fn forbidden() -> HttpResponse {
         HttpResponse::Forbidden().json(json!({
             "error": KErrorType::Forbidden,
+            "code": KErrorType::Forbidden.code(),
             "errorDescription": "Forbidden",
         }))
     }
 
+    pub fn unauthorized() -> HttpResponse {
+        HttpResponse::Unauthorized().json(json!({
+            "error": KErrorType::Unauthorized,
+            "code": KErrorType::Unauthorized.code(),
+            "errorDescription": "Authentication required",
+        }))
+    }
+
+    pub fn unsupported_media_type(details: &'_ str) -> HttpResponse {
+        HttpResponse::UnsupportedMediaType().json(json!({
+            "error": KErrorType::UnsupportedMediaType,
+            "code": KErrorType::UnsupportedMediaType.code(),
+            "errorDescription": details,
+        }))
+    }
+
+    pub fn payload_too_large(details: &'_ str) -> HttpResponse {
+        HttpResponse::PayloadTooLarge().json(json!({
+            "error": KErrorType::PayloadTooLarge,
+            "code": KErrorType::PayloadTooLarge.code(),
+            "errorDescription": details,
+        }))
+    }
+
+    /// The `Content-Range` header giving the actual length is left to the caller
+    pub fn range_not_satisfiable() -> HttpResponse {
+        HttpResponse::RangeNotSatisfiable().json(json!({
+            "error": KErrorType::RangeNotSatisfiable,
+            "code": KErrorType::RangeNotSatisfiable.code(),
+            "errorDescription": "Requested range is not satisfiable",
+        }))
+    }
+
+    pub fn conflict(details: &'_ str) -> HttpResponse {
+        HttpResponse::Conflict().json(json!({
+            "error": KErrorType::Conflict,
+            "code": KErrorType::Conflict.code(),
+            "errorDescription": details,
+        }))
+    }
+
+    pub fn conflict_with(details: &'_ str, conflicting_id: &'_ str) -> HttpResponse {
+        HttpResponse::Conflict().json(json!({
+            "error": KErrorType::Conflict,
+            "code": KErrorType::Conflict.code(),
+            "errorDescription": details,
+            "conflictingId": conflicting_id,
+        }))
+    }
+
     pub fn db_error() -> HttpResponse {
         HttpResponse::InternalServerError().json(json!({
             "error": KErrorType::InternalError,
+            "code": KErrorType::InternalError.code(),
             "errorDescription": "Could not retrieve data from database",
         }))
     }
@@ -79,6 +228,15 @@ impl<T> WithOID<T> {
     }
 }
 
+impl<T> From<WithID<T>> for WithOID<T> {
+    fn from(value: WithID<T>) -> Self {
+        Self {
+            id: value.id,
+            inner: value.inner,
+        }
+    }
+}
+
 impl<T> AsRef<T> for WithOID<T> {
     fn as_ref(&self) -> &T {
         &self.inner
@@ -136,7 +294,30 @@ pub struct AnimeReleaseInfo {
     pub release_year: u16,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExternalIds {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mal: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anilist: Option<u32>,
+}
+
+/// Serialized names of the [`ExternalIds`] fields, the sources we can resolve ids from
+pub const EXTERNAL_ID_SOURCES: [&str; 2] = ["mal", "anilist"];
+
+impl ExternalIds {
+    /// Pairs every source of [`EXTERNAL_ID_SOURCES`] with its id
+    pub fn entries(&self) -> [(&'static str, Option<u32>); 2] {
+        [("mal", self.mal), ("anilist", self.anilist)]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries().iter().all(|(_, id)| id.is_none())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub enum SeasonKind {
     Season,
@@ -145,6 +326,36 @@ pub enum SeasonKind {
     SpinOff,
 }
 
+pub const SEASON_KINDS: [SeasonKind; 4] = [SeasonKind::Season, SeasonKind::Movie, SeasonKind::Ova,
+    SeasonKind::SpinOff];
+
+impl SeasonKind {
+    /// Same as the serialized name
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SeasonKind::Season => "season",
+            SeasonKind::Movie => "movie",
+            SeasonKind::Ova => "ova",
+            SeasonKind::SpinOff => "spinOff",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<Self> {
+        SEASON_KINDS.into_iter().find(|k| k.as_str() == kind)
+    }
+}
+
+/// Every kind appearing in the mapping, in order of first appearance
+pub fn mapping_kinds(mapping: &[SeasonMapping]) -> Vec<SeasonKind> {
+    let mut kinds = Vec::with_capacity(SEASON_KINDS.len());
+    for entry in mapping {
+        if !kinds.contains(&entry.kind) {
+            kinds.push(entry.kind);
+        }
+    }
+    kinds
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct SeasonMapping {
@@ -159,6 +370,145 @@ pub struct SeasonMapping {
     pinned_note: Option<Note>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MappingUnit {
+    Episode,
+    Chapter,
+    Volume,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnitRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl UnitRange {
+    fn len(&self) -> u32 {
+        (self.end.saturating_sub(self.start) as u32) + 1
+    }
+
+    /// Maps the `sub` part of `self` proportionally onto `target`
+    fn project(&self, sub: UnitRange, target: UnitRange) -> UnitRange {
+        let ratio = target.len() as f32 / self.len() as f32;
+        // in u32 as an entry may end on the last u16
+        let offset_start = (sub.start as u32 - self.start as u32) as f32 * ratio;
+        let offset_end = (sub.end as u32 - self.start as u32 + 1) as f32 * ratio;
+        let last = target.end.max(target.start);
+        let start = target.start.saturating_add(offset_start.floor() as u16).min(last);
+        let end = target.start.saturating_add((offset_end.ceil() as u16).saturating_sub(1))
+            .clamp(start, last);
+        UnitRange { start, end }
+    }
+}
+
+/// Where a given episode, chapter or volume falls in the mapping of an anime, the ranges of the
+/// other units are estimated by assuming they are evenly spread over the matched entry
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MappingLocation {
+    pub index: usize,
+    pub mapping: SeasonMapping,
+    pub episodes: UnitRange,
+    pub chapters: UnitRange,
+    pub volumes: UnitRange,
+}
+
+impl SeasonMapping {
+    pub fn kind(&self) -> SeasonKind {
+        self.kind
+    }
+
+    pub fn range(&self, unit: MappingUnit) -> UnitRange {
+        match unit {
+            MappingUnit::Episode => UnitRange { start: self.start_episode, end: self.end_episode },
+            MappingUnit::Chapter => UnitRange { start: self.start_chapter, end: self.end_chapter },
+            MappingUnit::Volume => UnitRange { start: self.start_volume, end: self.end_volume },
+        }
+    }
+
+    pub fn contains(&self, unit: MappingUnit, n: u16) -> bool {
+        let range = self.range(unit);
+        range.start <= n && n <= range.end
+    }
+}
+
+fn validate_names<'a>(field: &str, names: impl ExactSizeIterator<Item=&'a str>,
+    limits: &CatalogConfig) -> Result<(), String> {
+    if names.len() > limits.max_titles() {
+        return Err(format!("At most {} {field} are allowed", limits.max_titles()));
+    }
+    for (i, name) in names.enumerate() {
+        if name.chars().count() > limits.max_title_length() {
+            return Err(format!("{field}[{i}] exceeds {} characters", limits.max_title_length()));
+        }
+    }
+    Ok(())
+}
+
+/// Checks the number and length of titles and synonyms against the catalog limits
+pub fn validate_titles(titles: &[Title], synonyms: &[String], limits: &CatalogConfig) -> Result<(), String> {
+    validate_names("titles", titles.iter().map(|t| t.value.as_str()), limits)?;
+    validate_names("synonyms", synonyms.iter().map(String::as_str), limits)
+}
+
+const MAX_ACCENT_COLOR: u32 = 0xFFFFFF;
+
+/// Checks that an accent color fits in 24 bits, written `0xRRGGBB`
+pub fn validate_accent_color(color: Option<u32>) -> Result<(), String> {
+    match color {
+        Some(color) if color > MAX_ACCENT_COLOR => Err(format!("accentColor {color:#x} is not a RGB color")),
+        _ => Ok(())
+    }
+}
+
+/// Checks that every range of each entry is ordered and that consecutive seasons do not overlap
+/// on episodes. Movies, OVAs and spin-offs have their own numbering and are only checked for
+/// ordered ranges.
+pub fn validate_mapping(mapping: &[SeasonMapping]) -> Result<(), String> {
+    for (i, entry) in mapping.iter().enumerate() {
+        for (unit, name) in [(MappingUnit::Episode, "episode"), (MappingUnit::Chapter, "chapter"),
+            (MappingUnit::Volume, "volume")] {
+            let range = entry.range(unit);
+            if range.start > range.end {
+                return Err(format!("mapping[{i}] starts at {name} {} after it ends at {name} {}",
+                    range.start, range.end));
+            }
+        }
+    }
+    let seasons: Vec<(usize, &SeasonMapping)> = mapping.iter().enumerate()
+        .filter(|(_, m)| matches!(m.kind, SeasonKind::Season))
+        .collect();
+    for pair in seasons.windows(2) {
+        let ((i, previous), (j, next)) = (pair[0], pair[1]);
+        if next.start_episode <= previous.end_episode {
+            return Err(format!("mapping[{j}] starts at episode {} before mapping[{i}] ends at episode {}",
+                next.start_episode, previous.end_episode));
+        }
+    }
+    Ok(())
+}
+
+/// Finds the first mapping entry containing `n` in the given unit
+pub fn locate_in_mapping(mapping: &[SeasonMapping], unit: MappingUnit, n: u16)
+    -> Option<MappingLocation> {
+    let (index, found) = mapping.iter().enumerate().find(|(_, m)| m.contains(unit, n))?;
+    let source = found.range(unit);
+    let point = UnitRange { start: n, end: n };
+    let locate = |target: MappingUnit| if target == unit {
+        point
+    } else {
+        source.project(point, found.range(target))
+    };
+    Some(MappingLocation {
+        index,
+        mapping: found.clone(),
+        episodes: locate(MappingUnit::Episode),
+        chapters: locate(MappingUnit::Chapter),
+        volumes: locate(MappingUnit::Volume),
+    })
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Note {
@@ -167,20 +517,38 @@ pub struct Note {
     content: String,
 }
 
+impl Note {
+    pub fn new(author: String, content: String) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .expect("The time can never be earlier than the Unix epoch")
+            .as_millis() as u64;
+        Self { timestamp, author, content }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct CachedImage {
     key: String,
-    placeholder: Option<String>
+    placeholder: Option<String>,
+    /// Dominant color as `0xRRGGBB`, absent on records created before it was stored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    accent: Option<u32>
 }
 
 impl CachedImage {
     pub fn new(key: String) -> Self {
-        Self { key, placeholder: None }
+        Self { key, placeholder: None, accent: None }
     }
 
     pub fn with_placeholder(key: String, placeholder: String) -> Self {
-        Self { key, placeholder: Some(placeholder) }
+        Self { key, placeholder: Some(placeholder), accent: None }
+    }
+
+    pub fn with_accent(mut self, accent: u32) -> Self {
+        self.accent = Some(accent);
+        self
     }
 
     pub fn key(&self) -> &str {
@@ -193,20 +561,169 @@ impl CachedImage {
             None => None
         }
     }
+
+    pub fn accent(&self) -> Option<u32> {
+        self.accent
+    }
+}
+
+pub const UNKNOWN_TITLE_LANGUAGE: &str = "unknown";
+
+/// A title in a given language, the `primary` one is displayed by default
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(from = "TitleRepr")]
+pub struct Title {
+    pub value: String,
+    pub language: String,
+    pub primary: bool,
+}
+
+fn unknown_title_language() -> String {
+    UNKNOWN_TITLE_LANGUAGE.to_string()
+}
+
+/// Titles used to be stored as plain strings, both shapes are accepted when deserializing
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TitleRepr {
+    Legacy(String),
+    Full {
+        value: String,
+        #[serde(default = "unknown_title_language")]
+        language: String,
+        #[serde(default)]
+        primary: bool,
+    },
+}
+
+impl From<TitleRepr> for Title {
+    fn from(repr: TitleRepr) -> Self {
+        match repr {
+            TitleRepr::Legacy(value) => Self {
+                value,
+                language: unknown_title_language(),
+                primary: false
+            },
+            TitleRepr::Full { value, language, primary } => Self { value, language, primary }
+        }
+    }
+}
+
+/// Returns the title marked as primary, or the first one if none is
+pub fn primary_title(titles: &[Title]) -> Option<&str> {
+    titles.iter()
+        .find(|t| t.primary)
+        .or_else(|| titles.first())
+        .map(|t| t.value.as_str())
+}
+
+/// Flattens the titles to their values, moving the primary one first so that consumers of the
+/// flattened form (like Meilisearch) can still tell it apart
+pub fn title_values(titles: &[Title]) -> Vec<String> {
+    let primary = titles.iter().position(|t| t.primary).unwrap_or(0);
+    let mut values: Vec<String> = titles.iter().map(|t| t.value.clone()).collect();
+    if primary < values.len() {
+        values[..=primary].rotate_right(1);
+    }
+    values
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct AnimeSeries {
-    pub titles: Vec<String>,
+    pub titles: Vec<Title>,
+    #[serde(default)]
+    pub synonyms: Vec<String>,
+    #[serde(default)]
+    pub normalized_titles: Vec<String>,
     pub poster: CachedImage,
     pub manga: MangaReleaseInfo,
     pub anime: AnimeReleaseInfo,
     pub mapping: Vec<SeasonMapping>,
+    #[serde(default)]
+    pub external_ids: ExternalIds,
+    /// Overrides the color derived from the poster on the presenter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accent_color: Option<u32>,
+    /// Unique and stable once assigned, animes created before slugs existed have none
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub slug: String,
     pub updated_on: u64,
     pub created_on: u64,
 }
 
+/// Lowercases the title and collapses any run of whitespace to a single space, so that titles
+/// differing only by casing or spacing compare equal
+pub fn normalize_title(title: &str) -> String {
+    title.split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn normalize_titles(titles: &[Title]) -> Vec<String> {
+    titles.iter().map(|t| normalize_title(&t.value)).collect()
+}
+
+const UNTITLED_SLUG: &str = "anime";
+
+/// Lowercases the title, strips accents and joins every run of letters and digits with a hyphen,
+/// `Kimi no Na wa.` becomes `kimi-no-na-wa`
+pub fn slugify(title: &str) -> String {
+    use unicode_normalization::{UnicodeNormalization, char::is_combining_mark};
+    let mut slug = String::with_capacity(title.len());
+    for c in title.nfkd().filter(|c| !is_combining_mark(*c)).flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let len = slug.trim_end_matches('-').len();
+    slug.truncate(len);
+    if slug.is_empty() {
+        slug.push_str(UNTITLED_SLUG);
+    }
+    slug
+}
+
+/// Serialized names of the [`AnimeSeries`] fields, usable in MongoDB projections
+pub const ANIME_SERIES_FIELDS: [&str; 11] = [
+    "titles", "synonyms", "poster", "manga", "anime", "mapping", "externalIds", "accentColor",
+    "slug", "updatedOn", "createdOn"
+];
+
+/// A field `GET /anime` can be sorted on, an index on it is created at startup
+#[derive(Debug, Clone, Copy)]
+pub struct ListSort {
+    /// Name accepted by the `sort` query parameter, prefixed with `-` to sort descending
+    pub name: &'static str,
+    /// Document path the sort and its index apply to
+    pub field: &'static str,
+}
+
+pub const ANIME_LIST_SORTS: [ListSort; 4] = [
+    ListSort { name: "updatedOn", field: "updatedOn" },
+    ListSort { name: "createdOn", field: "createdOn" },
+    ListSort { name: "title", field: "titles.value" },
+    ListSort { name: "slug", field: "slug" },
+];
+
+/// Parses a `sort` value such as `-createdOn` into the sort and its direction, `1` or `-1`
+pub fn parse_list_sort(value: &str) -> Option<(ListSort, i32)> {
+    let (name, direction) = match value.strip_prefix('-') {
+        Some(name) => (name, -1),
+        None => (value, 1)
+    };
+    ANIME_LIST_SORTS.iter().find(|sort| sort.name == name).map(|sort| (*sort, direction))
+}
+
+impl AnimeSeries {
+    pub fn primary_title(&self) -> Option<&str> {
+        primary_title(&self.titles)
+    }
+}
+
 impl AsRef<Self> for AnimeSeries {
     fn as_ref(&self) -> &Self {
         self
@@ -216,35 +733,162 @@ impl AsRef<Self> for AnimeSeries {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct AnimeSeriesCandidate {
-    pub titles: Vec<String>,
+    pub titles: Vec<Title>,
+    #[serde(default)]
+    pub synonyms: Vec<String>,
     pub manga: MangaReleaseInfo,
     pub anime: AnimeReleaseInfo,
     pub mapping: Vec<SeasonMapping>,
+    #[serde(default)]
+    pub external_ids: ExternalIds,
+    #[serde(default)]
+    pub accent_color: Option<u32>,
+}
+
+/// Paths of the object keys of `input` missing from `parsed`, that is the fields serde ignored
+/// when `parsed` is the serialized result of deserializing `input`. Null values carry nothing and
+/// values of another shape, such as legacy string titles, are not compared.
+fn ignored_fields(input: &serde_json::Value, parsed: &serde_json::Value, path: &str, found: &mut Vec<String>) {
+    use serde_json::Value;
+    match (input, parsed) {
+        (Value::Object(input), Value::Object(parsed)) => {
+            for (key, value) in input {
+                let field = if path.is_empty() { key.clone() } else { format!("{path}.{key}") };
+                match parsed.get(key) {
+                    Some(parsed) => ignored_fields(value, parsed, &field, found),
+                    None if !value.is_null() => found.push(field),
+                    None => {}
+                }
+            }
+        },
+        (Value::Array(input), Value::Array(parsed)) => {
+            for (i, (value, parsed)) in input.iter().zip(parsed).enumerate() {
+                ignored_fields(value, parsed, &format!("{path}[{i}]"), found);
+            }
+        },
+        _ => {}
+    }
 }
 
 impl AnimeSeriesCandidate {
+    /// Deserializes the candidate, fields it does not define are rejected when `strict`
+    pub fn from_json(input: serde_json::Value, strict: bool) -> Result<Self, String> {
+        if !strict {
+            return serde_json::from_value(input).map_err(|e| e.to_string());
+        }
+        let candidate: Self = serde_json::from_value(input.clone()).map_err(|e| e.to_string())?;
+        let parsed = serde_json::to_value(&candidate).expect("Candidates should always be serializable");
+        let mut unknown = Vec::new();
+        ignored_fields(&input, &parsed, "", &mut unknown);
+        if !unknown.is_empty() {
+            let unknown: Vec<String> = unknown.iter().map(|field| format!("`{field}`")).collect();
+            return Err(format!("unknown fields {}", unknown.join(", ")));
+        }
+        Ok(candidate)
+    }
+
+    pub fn validate(&self, limits: &CatalogConfig) -> Result<(), String> {
+        validate_titles(&self.titles, &self.synonyms, limits)?;
+        validate_accent_color(self.accent_color)?;
+        validate_mapping(&self.mapping)
+    }
+
     pub fn into_anime(self, poster: CachedImage) -> AnimeSeries {
         let now = SystemTime::now()
             .duration_since(SystemTime::UNIX_EPOCH)
             .expect("The time can never be earlier than the Unix epoch")
             .as_millis() as u64;
         AnimeSeries {
+            normalized_titles: normalize_titles(&self.titles),
             titles: self.titles,
+            synonyms: self.synonyms,
             poster,
             manga: self.manga,
             anime: self.anime,
             mapping: self.mapping,
+            external_ids: self.external_ids,
+            accent_color: self.accent_color,
+            // assigned on insertion as it has to be unique
+            slug: String::new(),
             updated_on: now,
             created_on: now
         }
     }
 }
 
+/// A patch field that can be removed: absent leaves it unchanged, `null` clears it and any other
+/// value replaces it. Only set values are serialized, cleared ones are handled by the caller.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum Clearable<T> {
+    #[default]
+    Unchanged,
+    Clear,
+    Set(T),
+}
+
+impl<T> Clearable<T> {
+    pub fn is_unchanged(&self) -> bool {
+        matches!(self, Clearable::Unchanged)
+    }
+
+    pub fn is_clear(&self) -> bool {
+        matches!(self, Clearable::Clear)
+    }
+
+    /// Whether nothing would be serialized for this field
+    pub fn is_not_set(&self) -> bool {
+        !matches!(self, Clearable::Set(_))
+    }
+
+    pub fn as_set(&self) -> Option<&T> {
+        match self {
+            Clearable::Set(value) => Some(value),
+            _ => None
+        }
+    }
+
+    /// The new value of an optional field, `None` if it is left unchanged
+    pub fn into_update(self) -> Option<Option<T>> {
+        match self {
+            Clearable::Unchanged => None,
+            Clearable::Clear => Some(None),
+            Clearable::Set(value) => Some(Some(value)),
+        }
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Clearable<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // only called when the field is present, absent fields use the default
+        Ok(match Option::<T>::deserialize(deserializer)? {
+            Some(value) => Clearable::Set(value),
+            None => Clearable::Clear,
+        })
+    }
+}
+
+impl<T: Serialize> Serialize for Clearable<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Clearable::Set(value) => value.serialize(serializer),
+            _ => serializer.serialize_none()
+        }
+    }
+}
+
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AnimeSeriesPatch {
     #[serde(skip_serializing_if = "Option::is_none")]
-    titles: Option<Vec<String>>,
+    titles: Option<Vec<Title>>,
+
+    /// Cleared by setting an empty list
+    #[serde(default, skip_serializing_if = "Clearable::is_not_set")]
+    synonyms: Clearable<Vec<String>>,
+
+    #[serde(skip_deserializing)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    normalized_titles: Option<Vec<String>>,
 
     #[serde(skip_deserializing)]
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -259,18 +903,66 @@ pub struct AnimeSeriesPatch {
     #[serde(skip_serializing_if = "Option::is_none")]
     mapping: Option<Vec<SeasonMapping>>,
 
+    #[serde(default, skip_serializing_if = "Clearable::is_not_set")]
+    external_ids: Clearable<ExternalIds>,
+
+    #[serde(default, skip_serializing_if = "Clearable::is_not_set")]
+    accent_color: Clearable<u32>,
+
     #[serde(skip_deserializing)]
     updated_on: u64,
 }
 
 impl AnimeSeriesPatch {
+    pub fn validate(&self, limits: &CatalogConfig) -> Result<(), String> {
+        validate_titles(self.titles.as_deref().unwrap_or_default(),
+            self.synonyms.as_set().map(Vec::as_slice).unwrap_or_default(), limits)?;
+        validate_accent_color(self.accent_color.as_set().copied())?;
+        match &self.mapping {
+            Some(mapping) => validate_mapping(mapping),
+            None => Ok(())
+        }
+    }
+
     pub fn is_empty(&self) -> bool {
-        self.titles.is_none() && self.poster.is_none() && self.manga.is_none()
-            && self.anime.is_none() && self.mapping.is_none()
+        self.titles.is_none() && self.synonyms.is_unchanged() && self.poster.is_none()
+            && self.manga.is_none() && self.anime.is_none() && self.mapping.is_none()
+            && self.external_ids.is_unchanged() && self.accent_color.is_unchanged()
+    }
+
+    /// The titles the patch sets, if any
+    pub fn titles(&self) -> Option<&[Title]> {
+        self.titles.as_deref()
+    }
+
+    /// The external ids the patch sets, if any
+    pub fn external_ids(&self) -> Option<&ExternalIds> {
+        self.external_ids.as_set()
     }
 
     pub fn has_presenter_changes(&self) -> bool {
         self.titles.is_some() || self.manga.is_some() || self.anime.is_some()
+            || !self.accent_color.is_unchanged()
+    }
+
+    /// Same as [`Self::has_presenter_changes`] but only considers the values rendered on the
+    /// presenter that actually differ from the `current` ones
+    pub fn differs_on_presenter(&self, current: &AnimeSeries) -> bool {
+        let title_changed = matches!(&self.titles,
+            Some(titles) if primary_title(titles) != current.primary_title());
+        let manga_changed = matches!(&self.manga,
+            Some(manga) if manga.chapters != current.manga.chapters
+                || manga.volumes != current.manga.volumes);
+        let anime_changed = matches!(&self.anime,
+            Some(anime) if anime.release_year != current.anime.release_year
+                || anime.episodes != current.anime.episodes
+                || anime.seasons != current.anime.seasons);
+        let accent_changed = match self.accent_color {
+            Clearable::Unchanged => false,
+            Clearable::Clear => current.accent_color.is_some(),
+            Clearable::Set(color) => Some(color) != current.accent_color,
+        };
+        title_changed || manga_changed || anime_changed || accent_changed
     }
 
     pub fn set_poster(&mut self, poster: CachedImage) {
@@ -280,9 +972,14 @@ impl AnimeSeriesPatch {
     pub fn apply(self, original: &mut AnimeSeries) {
         let mut updated = false;
         if let Some(titles) = self.titles {
+            original.normalized_titles = normalize_titles(&titles);
             original.titles = titles;
             updated = true;
         }
+        if let Some(synonyms) = self.synonyms.into_update() {
+            original.synonyms = synonyms.unwrap_or_default();
+            updated = true;
+        }
         if let Some(poster) = self.poster {
             original.poster = poster;
             updated = true;
@@ -299,6 +996,14 @@ impl AnimeSeriesPatch {
             original.mapping = mapping;
             updated = true;
         }
+        if let Some(external_ids) = self.external_ids.into_update() {
+            original.external_ids = external_ids.unwrap_or_default();
+            updated = true;
+        }
+        if let Some(accent_color) = self.accent_color.into_update() {
+            original.accent_color = accent_color;
+            updated = true;
+        }
         if updated {
             original.updated_on = SystemTime::now()
                 .duration_since(SystemTime::UNIX_EPOCH)
@@ -307,12 +1012,29 @@ impl AnimeSeriesPatch {
         }
     }
 
+    /// Builds the MongoDB update, cleared fields are unset or emptied
     pub fn seal(&mut self) -> Result<bson::Document, bson::ser::Error> {
+        self.normalized_titles = self.titles.as_deref().map(normalize_titles);
         self.updated_on = SystemTime::now()
             .duration_since(SystemTime::UNIX_EPOCH)
             .expect("The time can never be earlier than the Unix epoch")
             .as_millis() as u64;
-        bson::to_document(self)
+        let mut set = bson::to_document(self)?;
+        if self.synonyms.is_clear() {
+            set.insert("synonyms", bson::Array::new());
+        }
+        let mut unset = bson::Document::new();
+        for (field, cleared) in [("externalIds", self.external_ids.is_clear()),
+            ("accentColor", self.accent_color.is_clear())] {
+            if cleared {
+                unset.insert(field, "");
+            }
+        }
+        let mut update = bson::doc! { "$set": set };
+        if !unset.is_empty() {
+            update.insert("$unset", unset);
+        }
+        Ok(update)
     }
 }
 
@@ -338,11 +1060,46 @@ pub type MatchRanges = HashMap<String, Vec<MatchRange>>;
 pub struct AnimeSeriesSearchEntry {
     id: String,
     titles: Vec<String>,
+    #[serde(default)]
+    synonyms: Vec<String>,
     author: String,
     poster: CachedImage,
+    #[serde(default)]
+    release_year: u16,
+    /// Kinds of the mapping entries, filterable
+    #[serde(default)]
+    kinds: Vec<SeasonKind>,
     #[serde(rename(deserialize = "_matchesPosition"))]
     #[serde(skip_serializing_if = "Option::is_none")]
-    matches_position: Option<MatchRanges>
+    matches_position: Option<MatchRanges>,
+    /// Titles with the matched terms wrapped in `<em>` tags, only when highlighting is requested
+    #[serde(skip_deserializing)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    highlighted_titles: Option<Vec<String>>
+}
+
+fn non_empty_values(values: Vec<String>) -> Vec<String> {
+    values.into_iter()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+impl AnimeSeriesSearchEntry {
+    /// Trims the searchable values and drops the empty ones, fails when there is nothing
+    /// meaningful left to search on
+    pub fn normalized(mut self) -> Result<Self, String> {
+        self.titles = non_empty_values(self.titles);
+        self.synonyms = non_empty_values(self.synonyms);
+        self.author = self.author.trim().to_string();
+        if self.titles.is_empty() {
+            return Err("it has no title".to_string());
+        }
+        if self.author.is_empty() {
+            return Err("it has no author".to_string());
+        }
+        Ok(self)
+    }
 }
 
 impl From<meilisearch_sdk::search::SearchResult<Self>> for AnimeSeriesSearchEntry {
@@ -353,6 +1110,9 @@ impl From<meilisearch_sdk::search::SearchResult<Self>> for AnimeSeriesSearchEntr
                 .map(|(key, val)| (key, val.into_iter().map(|r| r.into()).collect()))
                 .collect()
         });
+        result.highlighted_titles = r.formatted_result
+            .and_then(|mut formatted| formatted.remove("titles"))
+            .and_then(|titles| serde_json::from_value(titles).ok());
         result
     }
 }
@@ -361,10 +1121,14 @@ impl From<WithOID<AnimeSeries>> for AnimeSeriesSearchEntry {
     fn from(value: WithOID<AnimeSeries>) -> Self {
         Self {
             id: value.id,
-            titles: value.inner.titles,
+            titles: title_values(&value.inner.titles),
+            synonyms: value.inner.synonyms,
             author: value.inner.manga.author,
             poster: value.inner.poster,
-            matches_position: None
+            release_year: value.inner.anime.release_year,
+            kinds: mapping_kinds(&value.inner.mapping),
+            matches_position: None,
+            highlighted_titles: None
         }
     }
 }
@@ -373,10 +1137,14 @@ impl From<WithID<AnimeSeries>> for AnimeSeriesSearchEntry {
     fn from(value: WithID<AnimeSeries>) -> Self {
         Self {
             id: value.id,
-            titles: value.inner.titles,
+            titles: title_values(&value.inner.titles),
+            synonyms: value.inner.synonyms,
             author: value.inner.manga.author,
             poster: value.inner.poster,
-            matches_position: None
+            release_year: value.inner.anime.release_year,
+            kinds: mapping_kinds(&value.inner.mapping),
+            matches_position: None,
+            highlighted_titles: None
         }
     }
 }
@@ -389,23 +1157,36 @@ pub struct AnimeSeriesSearchEntryPatch {
     #[serde(skip_serializing_if = "Option::is_none")]
     titles: Option<Vec<String>>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    synonyms: Option<Vec<String>>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     author: Option<String>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     poster: Option<CachedImage>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    release_year: Option<u16>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kinds: Option<Vec<SeasonKind>>,
 }
 
 impl AnimeSeriesSearchEntryPatch {
     pub fn from_patch(id: String, p: AnimeSeriesPatch) -> Option<Self> {
-        if p.titles.is_none() && p.manga.is_none() && p.poster.is_none() {
+        if p.titles.is_none() && p.synonyms.is_unchanged() && p.manga.is_none() && p.poster.is_none()
+            && p.anime.is_none() && p.mapping.is_none() {
             return None;
         }
         Some(Self {
             id,
-            titles: p.titles,
+            titles: p.titles.as_deref().map(title_values),
+            synonyms: p.synonyms.into_update().map(Option::unwrap_or_default),
             author: p.manga.map(|manga| manga.author),
-            poster: p.poster
+            poster: p.poster,
+            release_year: p.anime.map(|anime| anime.release_year),
+            kinds: p.mapping.as_deref().map(mapping_kinds)
         })
     }
 }
@@ -414,12 +1195,16 @@ pub fn get_search_entry() -> AnimeSeriesSearchEntry {
     AnimeSeriesSearchEntry {
         id: "63b44f977ef2f272e15f61ca".to_string(),
         titles: vec!["Tokyo Revengers".to_string()],
+        synonyms: vec!["Tokyo Ribenjāzu".to_string()],
         author: "Ken Wakui".to_string(),
         poster: CachedImage::with_placeholder(
             "d07f449fdeb9e559e19095db31da14ff".to_string(),
             "TFOBAk}sIT9r?ZI=u,$zKK#lNYx[".to_string(),
         ),
-        matches_position: None
+        release_year: 2021,
+        kinds: vec![SeasonKind::Season],
+        matches_position: None,
+        highlighted_titles: None
     }
 }
 
@@ -427,7 +1212,13 @@ pub fn get_anime() -> AnimeSeries {
     let now = SystemTime::now().duration_since(UNIX_EPOCH)
         .unwrap().as_millis() as u64;
     AnimeSeries {
-        titles: vec!["Tokyo Revengers".to_string()],
+        titles: vec![Title {
+            value: "Tokyo Revengers".to_string(),
+            language: "en".to_string(),
+            primary: true
+        }],
+        synonyms: vec!["Tokyo Ribenjāzu".to_string()],
+        normalized_titles: vec!["tokyo revengers".to_string()],
         poster: CachedImage::with_placeholder(
             "d07f449fdeb9e559e19095db31da14ff".to_string(),
             "TFOBAk}sIT9r?ZI=u,$zKK#lNYx[".to_string(),
@@ -457,7 +1248,72 @@ pub fn get_anime() -> AnimeSeries {
                 pinned_note: None,
             }
         ],
+        external_ids: ExternalIds {
+            mal: Some(42249),
+            anilist: Some(120120),
+        },
+        accent_color: None,
+        slug: "tokyo-revengers".to_string(),
         updated_on: now,
         created_on: now,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(kind: SeasonKind, episodes: (u16, u16), chapters: (u16, u16), volumes: (u16, u16)) -> SeasonMapping {
+        SeasonMapping {
+            kind,
+            label: String::new(),
+            start_episode: episodes.0,
+            end_episode: episodes.1,
+            start_chapter: chapters.0,
+            end_chapter: chapters.1,
+            start_volume: volumes.0,
+            end_volume: volumes.1,
+            pinned_note: None,
+        }
+    }
+
+    fn mapping() -> Vec<SeasonMapping> {
+        vec![
+            entry(SeasonKind::Season, (1, 24), (1, 73), (1, 8)),
+            entry(SeasonKind::Season, (25, 37), (74, 112), (9, 13)),
+            entry(SeasonKind::Ova, (1, 1), (10, 20), (2, 3)),
+        ]
+    }
+
+    #[test]
+    fn projects_episode_of_first_entry() {
+        let location = locate_in_mapping(&mapping(), MappingUnit::Episode, 1).unwrap();
+        assert_eq!(location.index, 0);
+        assert_eq!(location.episodes, UnitRange { start: 1, end: 1 });
+        assert_eq!(location.chapters, UnitRange { start: 1, end: 4 });
+        assert_eq!(location.volumes, UnitRange { start: 1, end: 1 });
+    }
+
+    #[test]
+    fn projects_episode_of_last_entry() {
+        let location = locate_in_mapping(&mapping(), MappingUnit::Episode, 37).unwrap();
+        assert_eq!(location.index, 1);
+        assert_eq!(location.chapters, UnitRange { start: 110, end: 112 });
+        assert_eq!(location.volumes, UnitRange { start: 13, end: 13 });
+    }
+
+    #[test]
+    fn projects_single_unit_entry_on_whole_range() {
+        let mapping = [entry(SeasonKind::Ova, (1, 1), (10, 20), (2, 3))];
+        let location = locate_in_mapping(&mapping, MappingUnit::Episode, 1).unwrap();
+        assert_eq!(location.chapters, UnitRange { start: 10, end: 20 });
+        assert_eq!(location.volumes, UnitRange { start: 2, end: 3 });
+    }
+
+    #[test]
+    fn projects_entry_ending_on_last_unit() {
+        let source = UnitRange { start: 0, end: u16::MAX };
+        let point = UnitRange { start: u16::MAX, end: u16::MAX };
+        assert_eq!(source.project(point, UnitRange { start: 1, end: 100 }), UnitRange { start: 100, end: 100 });
+    }
+}